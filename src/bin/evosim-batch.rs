@@ -0,0 +1,140 @@
+use std::time::Instant;
+
+use evosim::{cli::arg_value, config::Config, recorder::Recorder, *};
+
+// Headless runner: builds the same world as the viewer's reset() and steps it
+// as fast as possible, e.g.
+//   cargo run --release --no-default-features --bin evosim-batch -- --steps 1000000 --seed 42
+// '--config <path>' reads params and the initial population from a TOML file
+// (and where to record a CSV time series of the run),
+// '--load <path>' continues from a snapshot instead, '--save <path>' writes one
+// at the end ('.json' for JSON, anything else for the compact binary format)
+// and '--newick <path>' writes the phylogeny of the run as a Newick tree
+
+fn print_status(step: usize, world: &World) {
+    let plant_total: f32 = world.plant_sources.values().map(|p| p.amount).sum();
+    let meat_total: f32 = world.meat_sources.values().map(|m| m.amount).sum();
+    println!(
+        "step {:>10} | t = {:>10.2} | creatures {:>6} | plants {:>6} ({:>10.1}) | meat {:>6} ({:>10.1})",
+        step,
+        world.params.time,
+        world.creatures.len(),
+        world.plant_sources.len(),
+        plant_total,
+        world.meat_sources.len(),
+        meat_total,
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let steps: usize = arg_value(&args, "--steps").unwrap_or(100_000);
+    let report_every: usize = arg_value(&args, "--report-every").unwrap_or(10_000);
+    let config_path: Option<String> = arg_value(&args, "--config");
+
+    let load: Option<String> = arg_value(&args, "--load");
+    let save: Option<String> = arg_value(&args, "--save");
+    let newick: Option<String> = arg_value(&args, "--newick");
+
+    let mut config = match &config_path {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            eprintln!("invalid config {}", e);
+            std::process::exit(2);
+        }),
+        None => Config::default(),
+    };
+    let mut recorder = config.recorder.path.as_ref().map(|path| {
+        Recorder::create(path, config.recorder.every).unwrap_or_else(|e| {
+            eprintln!("could not create recording '{}': {}", path, e);
+            std::process::exit(1);
+        })
+    });
+
+    let (mut world, mut rng) = match &load {
+        Some(path) => World::load(path).unwrap_or_else(|e| {
+            eprintln!("could not load snapshot '{}': {}", path, e);
+            std::process::exit(1);
+        }),
+        None => {
+            // '--seed' overrides the seed from the config, and without either
+            // the run gets a fresh one (printed below so it can be repeated)
+            config.params.seed = arg_value(&args, "--seed")
+                .or(config.seed)
+                .unwrap_or_else(rand::random);
+            let mut rng = seeded_rng(config.params.seed);
+            (random_world(&mut rng, &config), rng)
+        }
+    };
+
+    println!("seed {}, running {} steps", world.params.seed, steps);
+    print_status(0, &world);
+    let start = Instant::now();
+    let record = |recorder: &mut Option<Recorder>, result: std::io::Result<()>| {
+        if let Err(e) = result {
+            eprintln!("stopped recording: {}", e);
+            *recorder = None;
+        }
+    };
+    if let Some(r) = recorder.as_mut() {
+        let result = r.sample(&world);
+        record(&mut recorder, result);
+    }
+    for step in 1..=steps {
+        update_world(&mut rng, &mut world);
+        if let Some(r) = recorder.as_mut() {
+            let result = r.step(&world);
+            record(&mut recorder, result);
+        }
+        if report_every > 0 && step % report_every == 0 {
+            print_status(step, &world);
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    if let Some(r) = recorder.as_mut() {
+        let result = r.flush();
+        record(&mut recorder, result);
+    }
+
+    println!("--- summary ---");
+    print_status(steps, &world);
+    let stats = &world.stats;
+    println!(
+        "births: {} sexual, {} asexual; deaths: {} starved, {} eaten, {} of old age",
+        stats.sexual_births,
+        stats.asexual_births,
+        stats.starvation_deaths,
+        stats.predation_deaths,
+        stats.old_age_deaths
+    );
+    if let Some(top) = world.creatures.values().max_by_key(|c| c.kills) {
+        println!("most kills by a living creature: {}", top.kills);
+    }
+    println!(
+        "phylogeny: {} lineage records",
+        world.phylogeny.lineages.len()
+    );
+    if let Some(path) = &newick {
+        let tree = world.phylogeny.to_newick(world.params.time);
+        match std::fs::write(path, tree + "\n") {
+            Ok(()) => println!("wrote phylogeny to {}", path),
+            Err(e) => {
+                eprintln!("could not write phylogeny '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &save {
+        match world.save(&rng, path) {
+            Ok(()) => println!("saved snapshot to {}", path),
+            Err(e) => {
+                eprintln!("could not save snapshot '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    println!(
+        "wall time {:.3} s ({:.0} steps/s)",
+        elapsed,
+        steps as f64 / elapsed.max(1e-9)
+    );
+}
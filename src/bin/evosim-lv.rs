@@ -0,0 +1,116 @@
+use std::fs;
+
+use evosim::{cli::arg_value, lotka_volterra::*};
+
+// Compares a recorded run with Lotka-Volterra dynamics: reads the prey and
+// predators columns of a Recorder CSV, fits the classic and logistic-prey
+// models and reports fit quality, cycle period and phase lag for the run and
+// for each fitted model, e.g.
+//   cargo run --release --no-default-features --bin evosim-lv -- --csv run.csv --from 100
+
+#[derive(Default)]
+struct Series {
+    times: Vec<f64>,
+    prey: Vec<f64>,
+    predators: Vec<f64>,
+}
+
+fn read_series(path: &str, from: f64) -> Result<Series, String> {
+    // time, prey and predators columns for rows at or after 'from'
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| format!("{}: no '{}' column", path, name))
+    };
+    let columns = [column("time")?, column("prey")?, column("predators")?];
+    let mut series = Series::default();
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        let mut values = [0.; 3];
+        for (value, &c) in values.iter_mut().zip(&columns) {
+            *value = fields
+                .get(c)
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| format!("{}: bad value on line {}", path, i + 2))?;
+        }
+        if values[0] >= from {
+            series.times.push(values[0]);
+            series.prey.push(values[1]);
+            series.predators.push(values[2]);
+        }
+    }
+    Ok(series)
+}
+
+fn print_cycles(label: &str, times: &[f64], prey: &[f64], predators: &[f64]) {
+    let show = |v: Option<f64>| v.map_or("none".to_string(), |v| format!("{:.2}", v));
+    let prey_period = period(times, prey);
+    let lag = phase_lag(times, prey, predators);
+    let lag_fraction = prey_period.zip(lag).map(|(p, l)| l / p);
+    println!(
+        "{:<14} period prey {} / predators {}, predators lag by {} ({} of a period)",
+        label,
+        show(prey_period),
+        show(period(times, predators)),
+        show(lag),
+        show(lag_fraction),
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = arg_value::<String>(&args, "--csv") else {
+        eprintln!("usage: evosim-lv --csv <recording.csv> [--from <time>]");
+        std::process::exit(2);
+    };
+    let from: f64 = arg_value(&args, "--from").unwrap_or(f64::NEG_INFINITY);
+    let Series {
+        times,
+        prey,
+        predators,
+    } = read_series(&path, from).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    println!(
+        "{} samples from t = {:.2}",
+        times.len(),
+        times.first().unwrap_or(&0.)
+    );
+    print_cycles("simulation", &times, &prey, &predators);
+    for model in [LvModel::Classic, LvModel::LogisticPrey] {
+        let Some(fit) = fit(model, &times, &prey, &predators) else {
+            println!("{:?}: not enough variation in the data to fit", model);
+            continue;
+        };
+        let p = fit.params;
+        let (x, y) = p.equilibrium();
+        println!(
+            "{:?}: alpha {:.4e}, beta {:.4e}, gamma {:.4e}, delta {:.4e}, capacity {:.4e}",
+            model, p.alpha, p.beta, p.gamma, p.delta, p.capacity
+        );
+        println!(
+            "  R^2 {:.3} (prey {:.3}, predators {:.3}), RMSE prey {:.2}, predators {:.2}",
+            fit.r_squared(),
+            fit.prey_r_squared,
+            fit.predator_r_squared,
+            fit.prey_rmse,
+            fit.predator_rmse
+        );
+        println!(
+            "  equilibrium prey {:.1}, predators {:.1}, small-cycle period {}",
+            x,
+            y,
+            p.linear_period()
+                .map_or("none".to_string(), |t| format!("{:.2}", t))
+        );
+        let (model_prey, model_predators): (Vec<f64>, Vec<f64>) =
+            fit.trajectory(&times).into_iter().unzip();
+        print_cycles("  fitted model", &times, &model_prey, &model_predators);
+    }
+}
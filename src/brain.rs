@@ -0,0 +1,66 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use crate::Params;
+
+// Network shape: sensor inputs (the last one a constant bias), tanh hidden
+// units and steering outputs
+pub const BRAIN_INPUTS: usize = 12;
+pub const BRAIN_HIDDEN: usize = 6;
+pub const BRAIN_OUTPUTS: usize = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Brain {
+    /*
+     * A small feed-forward network stored inline (fixed-size arrays) so that
+     * Genome and Creature stay Copy. Inputs are documented on
+     * controller::Neural::sense; the two outputs in [-1, 1] are the desired
+     * velocity as a fraction of max speed.
+     */
+    pub hidden: [[f32; BRAIN_INPUTS]; BRAIN_HIDDEN],
+    pub output: [[f32; BRAIN_HIDDEN + 1]; BRAIN_OUTPUTS], // last column is the bias
+}
+
+impl Brain {
+    pub fn new_rand<R: Rng>(rng: &mut R) -> Self {
+        // Small Gaussian weights so founders start out with gentle, varied
+        // behaviour rather than saturated outputs
+        let mut brain = Brain::default();
+        for w in brain.weights_mut() {
+            *w = 0.5 * rng.sample::<f32, _>(StandardNormal);
+        }
+        brain
+    }
+
+    fn weights_mut(&mut self) -> impl Iterator<Item = &mut f32> {
+        self.hidden
+            .iter_mut()
+            .flatten()
+            .chain(self.output.iter_mut().flatten())
+    }
+
+    pub fn think(&self, inputs: &[f32; BRAIN_INPUTS]) -> [f32; BRAIN_OUTPUTS] {
+        let hidden: [f32; BRAIN_HIDDEN] = std::array::from_fn(|i| {
+            let sum: f32 = self.hidden[i].iter().zip(inputs).map(|(w, x)| w * x).sum();
+            sum.tanh()
+        });
+        std::array::from_fn(|o| {
+            let weights = &self.output[o];
+            let sum: f32 = weights.iter().zip(&hidden).map(|(w, h)| w * h).sum();
+            (sum + weights[BRAIN_HIDDEN]).tanh()
+        })
+    }
+
+    pub fn mutate<R: Rng>(&self, rng: &mut R, params: &Params) -> Brain {
+        // Like the other genes each weight mutates with probability
+        // mutation_rate, but additively since weights are centred on zero
+        let mut brain = *self;
+        for w in brain.weights_mut() {
+            if rng.random::<f32>() < params.mutation_rate {
+                *w += params.mutation_sigma * rng.sample::<f32, _>(StandardNormal);
+            }
+        }
+        brain
+    }
+}
@@ -0,0 +1,19 @@
+use std::str::FromStr;
+
+// Command line handling shared by the binaries
+
+pub fn arg_value<T: FromStr>(args: &[String], flag: &str) -> Option<T> {
+    // Parse the value following 'flag', exiting with a message if it is bad
+    let i = args.iter().position(|a| a == flag)?;
+    match args.get(i + 1).map(|s| s.parse::<T>()) {
+        Some(Ok(v)) => Some(v),
+        _ => {
+            eprintln!(
+                "'{}' expects a value of type {}",
+                flag,
+                std::any::type_name::<T>()
+            );
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+// Plain RGBA colour so the core does not depend on a rendering crate; the
+// viewer converts it into a macroquad Color
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+// Same values as the macroquad palette
+pub const WHITE: Color = Color::new(1.00, 1.00, 1.00, 1.00);
+pub const BLUE: Color = Color::new(0.00, 0.47, 0.95, 1.00);
+pub const BROWN: Color = Color::new(0.50, 0.42, 0.31, 1.00);
+pub const GOLD: Color = Color::new(1.00, 0.80, 0.00, 1.00);
+pub const RED: Color = Color::new(0.90, 0.16, 0.22, 1.00);
+
+#[cfg(feature = "viewer")]
+impl From<Color> for macroquad::color::Color {
+    fn from(c: Color) -> Self {
+        macroquad::color::Color::new(c.r, c.g, c.b, c.a)
+    }
+}
@@ -0,0 +1,303 @@
+use std::{f32::consts::PI, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Params;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /*
+     * Everything needed to set up a run, read from a TOML file. Every table and
+     * field is optional and falls back to its default, e.g.
+     *
+     *   [params]
+     *   world_width = 3200.0
+     *   mutation_rate = 0.05
+     *
+     *   [population]
+     *   creatures = 100
+     *
+     *   [traits]
+     *   diet = [0.0, 0.3]
+     *
+     *   [recorder]
+     *   path = "run.csv"
+     */
+    pub params: Params,
+    pub population: Population,
+    pub traits: TraitRanges,
+    pub recorder: RecorderConfig,
+    // params.seed if the file sets one; runs without it draw a fresh seed
+    #[serde(skip)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Population {
+    // number of each entity in a freshly generated world
+    pub plants: usize,
+    pub meat: usize,
+    pub creatures: usize,
+    pub neural_fraction: f32, // chance for each founder to get a random neural brain
+}
+
+impl Default for Population {
+    fn default() -> Population {
+        Population {
+            plants: 10,
+            meat: 5,
+            creatures: 20,
+            neural_fraction: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraitRanges {
+    /*
+     * Founder creatures draw each trait uniformly from [lo, hi]; lo == hi
+     * gives every founder the same value. hunger_threshold is a fraction of
+     * the founder's starting hunger.
+     */
+    pub hunger: (f32, f32),
+    pub hunger_threshold: (f32, f32),
+    pub hunger_rate: (f32, f32),
+    pub dexterity: (f32, f32),
+    pub mate_preference: (f32, f32),
+    pub diet: (f32, f32),
+    pub strength: (f32, f32),
+    pub boldness: (f32, f32),
+    pub max_age: (f32, f32),
+    pub adult_size: (f32, f32),
+    pub vision_range: (f32, f32),
+    pub field_of_view: (f32, f32),
+    pub smell: (f32, f32),
+    pub density: (f32, f32), // relative to water
+}
+
+impl Default for TraitRanges {
+    fn default() -> TraitRanges {
+        TraitRanges {
+            hunger: (10., 100.),
+            hunger_threshold: (0.25, 0.75),
+            hunger_rate: (1e-4, 1e-3),
+            dexterity: (1., 1.),
+            mate_preference: (0.1, 0.5),
+            diet: (0., 1.),
+            strength: (0.5, 1.5),
+            boldness: (0., 1.),
+            max_age: (800., 1200.),
+            adult_size: (0.8, 1.2),
+            vision_range: (150., 300.),
+            field_of_view: (0.5 * PI, 2. * PI),
+            smell: (0.5, 1.5),
+            density: (0.95, 1.1),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    // CSV time series of the run, see Recorder; nothing is written without a path
+    pub path: Option<String>,
+    pub every: usize, // steps between samples
+}
+
+impl Default for RecorderConfig {
+    fn default() -> RecorderConfig {
+        RecorderConfig {
+            path: None,
+            every: 100,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Config> {
+        // Read and validate a config file; the error names the file and, for
+        // bad values, every field that is out of range
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let mut config: Config = toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        // Params::default() has seed 0, so whether the file picked one has to
+        // be looked up in the TOML itself
+        let table: toml::Table = toml::from_str(&text).unwrap_or_default();
+        if table.get("params").and_then(|p| p.get("seed")).is_some() {
+            config.seed = Some(config.params.seed);
+        }
+        config.validate().map_err(|problems| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:\n  {}", path.display(), problems.join("\n  ")),
+            )
+        })?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        // Collect a message for each value the simulation can't work with
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, field: &str, rule: &str, value: String| {
+            if !ok {
+                problems.push(format!("{} must be {} (got {})", field, rule, value));
+            }
+        };
+        let positive = |field: &str, v: f32| (v > 0., field.to_string(), "> 0", v);
+        let unit =
+            |field: &str, v: f32| ((0. ..=1.).contains(&v), field.to_string(), "in [0, 1]", v);
+        let non_negative = |field: &str, v: f32| (v >= 0., field.to_string(), ">= 0", v);
+
+        let p = &self.params;
+        for (ok, field, rule, v) in [
+            positive("params.window_width", p.window_width),
+            positive("params.window_height", p.window_height),
+            positive("params.world_width", p.world_width),
+            positive("params.world_height", p.world_height),
+            non_negative("params.padding", p.padding),
+            positive("params.plant_regrow_freq", p.plant_regrow_freq),
+            positive("params.timestep", p.timestep),
+            unit("params.damping", p.damping),
+            non_negative("params.gravity", p.gravity),
+            non_negative("params.hover_cost", p.hover_cost),
+            positive("params.reproduction_hunger", p.reproduction_hunger),
+            non_negative("params.reproduction_cost", p.reproduction_cost),
+            non_negative("params.reproduction_cooldown", p.reproduction_cooldown),
+            non_negative("params.mate_search_radius", p.mate_search_radius),
+            non_negative("params.mate_search_time", p.mate_search_time),
+            non_negative("params.plant_nutrition", p.plant_nutrition),
+            non_negative("params.meat_nutrition", p.meat_nutrition),
+            unit("params.hunt_min_diet", p.hunt_min_diet),
+            non_negative("params.hunt_cost", p.hunt_cost),
+            non_negative("params.threat_radius", p.threat_radius),
+            positive("params.grid_cell_size", p.grid_cell_size),
+            non_negative("params.hp_regen", p.hp_regen),
+            non_negative("params.senescence_rate", p.senescence_rate),
+            positive("params.max_prey_mass_ratio", p.max_prey_mass_ratio),
+            non_negative("params.attack_damage", p.attack_damage),
+            non_negative("params.meat_per_body_mass", p.meat_per_body_mass),
+            positive("params.meat_half_life", p.meat_half_life),
+            non_negative("params.meat_min_amount", p.meat_min_amount),
+            unit("params.meat_decay_to_plants", p.meat_decay_to_plants),
+            non_negative("params.meat_fertilise_radius", p.meat_fertilise_radius),
+            non_negative("params.light_attenuation", p.light_attenuation),
+            unit("params.light_compensation", p.light_compensation),
+            non_negative("params.current_speed", p.current_speed),
+            positive("params.current_scale", p.current_scale),
+            non_negative("params.current_period", p.current_period),
+            non_negative("params.plant_dispersal_time", p.plant_dispersal_time),
+            non_negative(
+                "params.phylogeny_prune_interval",
+                p.phylogeny_prune_interval,
+            ),
+            non_negative("params.vision_cost", p.vision_cost),
+            positive("params.scent_cell_size", p.scent_cell_size),
+            non_negative("params.scent_emission", p.scent_emission),
+            non_negative("params.scent_diffusion", p.scent_diffusion),
+            non_negative("params.scent_decay", p.scent_decay),
+            positive("params.scent_threshold", p.scent_threshold),
+            non_negative("params.smell_cost", p.smell_cost),
+            unit(
+                "population.neural_fraction",
+                self.population.neural_fraction,
+            ),
+            unit("params.mutation_rate", p.mutation_rate),
+            non_negative("params.mutation_sigma", p.mutation_sigma),
+        ] {
+            check(ok, &field, rule, v.to_string());
+        }
+        check(
+            2. * p.padding < p.world_width.min(p.world_height),
+            "params.padding",
+            "less than half the world size",
+            p.padding.to_string(),
+        );
+
+        // Hunger is capped at 100, and a parent mustn't pay more than it has
+        check(
+            p.reproduction_hunger <= 100.,
+            "params.reproduction_hunger",
+            "<= 100",
+            p.reproduction_hunger.to_string(),
+        );
+        check(
+            p.reproduction_cost <= p.reproduction_hunger,
+            "params.reproduction_cost",
+            "<= params.reproduction_hunger",
+            p.reproduction_cost.to_string(),
+        );
+
+        check(
+            self.recorder.every > 0,
+            "recorder.every",
+            "> 0",
+            self.recorder.every.to_string(),
+        );
+
+        // Trait ranges need lo <= hi and both ends inside the gene's limits
+        let t = &self.traits;
+        for (field, (lo, hi), min, max) in [
+            ("traits.hunger", t.hunger, f32::MIN_POSITIVE, 100.),
+            ("traits.hunger_threshold", t.hunger_threshold, 0., 1.),
+            (
+                "traits.hunger_rate",
+                t.hunger_rate,
+                f32::MIN_POSITIVE,
+                f32::MAX,
+            ),
+            ("traits.dexterity", t.dexterity, f32::MIN_POSITIVE, f32::MAX),
+            ("traits.mate_preference", t.mate_preference, 0., 1.),
+            ("traits.diet", t.diet, 0., 1.),
+            ("traits.strength", t.strength, f32::MIN_POSITIVE, f32::MAX),
+            ("traits.boldness", t.boldness, 0., 1.),
+            ("traits.max_age", t.max_age, f32::MIN_POSITIVE, f32::MAX),
+            (
+                "traits.adult_size",
+                t.adult_size,
+                f32::MIN_POSITIVE,
+                f32::MAX,
+            ),
+            (
+                "traits.vision_range",
+                t.vision_range,
+                f32::MIN_POSITIVE,
+                f32::MAX,
+            ),
+            (
+                "traits.field_of_view",
+                t.field_of_view,
+                f32::MIN_POSITIVE,
+                2. * PI,
+            ),
+            ("traits.smell", t.smell, f32::MIN_POSITIVE, f32::MAX),
+            ("traits.density", t.density, f32::MIN_POSITIVE, f32::MAX),
+        ] {
+            let rule = match (min, max) {
+                (_, f32::MAX) => "a [lo, hi] range with 0 < lo <= hi".to_string(),
+                (f32::MIN_POSITIVE, _) => format!("a [lo, hi] range with 0 < lo <= hi <= {}", max),
+                _ => format!("a [lo, hi] range with {} <= lo <= hi <= {}", min, max),
+            };
+            check(
+                min <= lo && lo <= hi && hi <= max,
+                field,
+                &rule,
+                format!("[{}, {}]", lo, hi),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
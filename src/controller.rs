@@ -0,0 +1,184 @@
+use glam::Vec2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Creature, Target, World, brain::BRAIN_INPUTS, find_food, find_random_walk_target, find_threat,
+    follow_scent, spatial::EntityKind,
+};
+
+// Distance at which a creature can eat or bite something, the same as
+// move_to_target's "close enough"
+const REACH: f32 = 5.;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerKind {
+    #[default]
+    HandCoded, // flee threats, chase the best food when hungry, else wander
+    Neural, // steered by the Brain in the creature's genome
+}
+
+pub trait Controller {
+    // Decide where the creature goes this step and move it, eating, hunting
+    // or mating with whatever it reaches. Returns any offspring together with
+    // the ID of the mate it was had with
+    fn act<R: Rng>(
+        &self,
+        rng: &mut R,
+        creature: &mut Creature,
+        id: usize,
+        world: &mut World,
+    ) -> Option<(Creature, usize)>;
+}
+
+impl ControllerKind {
+    pub fn act<R: Rng>(
+        self,
+        rng: &mut R,
+        creature: &mut Creature,
+        id: usize,
+        world: &mut World,
+    ) -> Option<(Creature, usize)> {
+        match self {
+            ControllerKind::HandCoded => HandCoded.act(rng, creature, id, world),
+            ControllerKind::Neural => Neural.act(rng, creature, id, world),
+        }
+    }
+}
+
+pub struct HandCoded;
+
+impl Controller for HandCoded {
+    fn act<R: Rng>(
+        &self,
+        rng: &mut R,
+        creature: &mut Creature,
+        id: usize,
+        world: &mut World,
+    ) -> Option<(Creature, usize)> {
+        if let Some(away) = find_threat(creature, id, world) {
+            // Safety first: drop whatever we were doing and run
+            creature.movement_target = None;
+            creature.flee(away, world);
+            return None;
+        }
+        if creature.is_hungry() && creature.movement_target.is_none() {
+            find_food(creature, id, world);
+            if creature.movement_target.is_none() {
+                follow_scent(creature, world);
+            }
+        }
+        // Wander when there is nothing to do and no food in sight or smell
+        find_random_walk_target(rng, creature, world);
+        if creature.move_to_target(world) {
+            creature.handle_reached_target(rng, world)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Neural;
+
+impl Neural {
+    fn sense(&self, creature: &Creature, id: usize, world: &World) -> [f32; BRAIN_INPUTS] {
+        // Brain inputs, all roughly in [-1, 1]:
+        //   0, 1: direction to the nearest visible food of the kind it digests best
+        //   2:    closeness of that food (1 on top of it, 0 at the edge of vision or none)
+        //   3:    hunger / 100
+        //   4, 5: velocity as a fraction of max speed
+        //   6, 7: direction to the nearest other visible creature
+        //   8:    closeness of that creature
+        //   9, 10: direction up the scent gradient of its food (zero if it smells none)
+        //   11:   constant bias
+        let radius = creature.genome.vision_range;
+        let sense = |kind: EntityKind, accept: &dyn Fn(usize) -> bool| {
+            let Some((other_id, distance)) = world.nearest_visible(creature, kind, radius, accept)
+            else {
+                return (Vec2::ZERO, 0.);
+            };
+            let position = match kind {
+                EntityKind::Creature => world.creatures[&other_id].position,
+                EntityKind::Plant => world.plant_sources[&other_id].position,
+                EntityKind::Meat => world.meat_sources[&other_id].position,
+            };
+            let direction = world
+                .displacement(creature.position, position)
+                .normalize_or_zero();
+            (direction, 1. - distance / radius)
+        };
+        let food_kind = if creature.plant_efficiency() >= creature.meat_efficiency() {
+            EntityKind::Plant
+        } else {
+            EntityKind::Meat
+        };
+        let (food, food_closeness) = sense(food_kind, &|_| true);
+        let (other, other_closeness) = sense(EntityKind::Creature, &|other| other != id);
+        let scent = creature.smell_food(world).unwrap_or(Vec2::ZERO);
+        let velocity = creature.velocity / creature.max_speed();
+        [
+            food.x,
+            food.y,
+            food_closeness,
+            creature.hunger / 100.,
+            velocity.x,
+            velocity.y,
+            other.x,
+            other.y,
+            other_closeness,
+            scent.x,
+            scent.y,
+            1.,
+        ]
+    }
+
+    fn in_reach(&self, creature: &Creature, id: usize, world: &World) -> Option<Target> {
+        // Something edible within reach: food it can digest first, then prey
+        let position = creature.position;
+        let nearby = |kind| world.nearest(position, kind, REACH, |other| other != id);
+        if creature.plant_efficiency() > 0.
+            && let Some((food_id, _)) = nearby(EntityKind::Plant)
+        {
+            return Some(Target::Food(food_id));
+        }
+        if creature.meat_efficiency() > 0. {
+            if let Some((meat_id, _)) = nearby(EntityKind::Meat) {
+                return Some(Target::Meat(meat_id));
+            }
+            let prey = world.nearest(position, EntityKind::Creature, REACH, |prey_id| {
+                prey_id != id && creature.can_hunt(&world.creatures[&prey_id], &world.params)
+            });
+            if let Some((prey_id, _)) = prey {
+                return Some(Target::Creature(prey_id));
+            }
+        }
+        None
+    }
+}
+
+impl Controller for Neural {
+    fn act<R: Rng>(
+        &self,
+        rng: &mut R,
+        creature: &mut Creature,
+        id: usize,
+        world: &mut World,
+    ) -> Option<(Creature, usize)> {
+        // Finding a mate stays hand-coded so both controllers breed alike
+        if let Some(Target::Mate(_)) = creature.movement_target {
+            return if creature.move_to_target(world) {
+                creature.handle_reached_target(rng, world)
+            } else {
+                None
+            };
+        }
+        let inputs = self.sense(creature, id, world);
+        let [x, y] = creature.genome.brain.think(&inputs);
+        creature.steer(Vec2::new(x, y) * creature.max_speed(), world);
+        // Eat or bite whatever the brain steered us onto
+        creature.movement_target = self.in_reach(creature, id, world);
+        let offspring = creature.handle_reached_target(rng, world);
+        creature.movement_target = None;
+        offspring
+    }
+}
@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+use glam::Vec2;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{BoundaryMode, Bounds, Params, SimRng};
+
+// Number of waves summed for curl noise
+const NOISE_MODES: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurrentKind {
+    #[default]
+    None, // still water
+    Constant,  // the same flow everywhere, along current_direction
+    Shear,     // along x, current_speed at the surface to -current_speed at the floor
+    Vortex,    // a gyre filling the basin (two with periodic x so they meet at the seam)
+    CurlNoise, // swirling eddies about current_scale across
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Wave {
+    wave_vector: Vec2,
+    phase: f32,
+    drift: f32, // how fast the phase moves, in turns per current_period
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CurrentField {
+    /*
+     * Water velocity anywhere in the world, shaped by the current_* params.
+     * Every kind is divergence free, so currents swirl things around rather
+     * than piling them up. Curl noise is the curl of a sum of plane waves
+     * with random directions and phases; the waves are drawn once from their
+     * own RNG seeded by params.seed, so turning currents on doesn't change
+     * any other random draw. With a current_period the other kinds reverse
+     * like a tide and the noise eddies drift.
+     */
+    waves: Vec<Wave>,
+}
+
+impl CurrentField {
+    pub fn new(params: &Params, bounds: &Bounds) -> Self {
+        if params.current != CurrentKind::CurlNoise {
+            return Self::default();
+        }
+        let mut rng = SimRng::seed_from_u64(params.seed ^ 0xC0FFEE);
+        let k = 2. * PI / params.current_scale;
+        let waves = (0..NOISE_MODES)
+            .map(|_| {
+                let mut wave_vector = k * Vec2::from_angle(rng.random_range(0.0..2. * PI));
+                if params.boundary == BoundaryMode::PeriodicX {
+                    // Whole wavelengths across the world so the seam is smooth
+                    let kx = 2. * PI / bounds.width();
+                    wave_vector.x = (wave_vector.x / kx).round() * kx;
+                }
+                let drift = rng.random_range(0.5..1.5) * if rng.random() { 1. } else { -1. };
+                Wave {
+                    wave_vector,
+                    phase: rng.random_range(0.0..2. * PI),
+                    drift,
+                }
+            })
+            .collect();
+        Self { waves }
+    }
+
+    pub fn velocity(&self, params: &Params, bounds: &Bounds, position: Vec2) -> Vec2 {
+        let speed = params.current_speed;
+        let turns = if params.current_period > 0. {
+            params.time / params.current_period
+        } else {
+            0.
+        };
+        let tide = (2. * PI * turns).cos();
+        match params.current {
+            CurrentKind::None => Vec2::ZERO,
+            CurrentKind::Constant => tide * speed * Vec2::from_angle(params.current_direction),
+            CurrentKind::Shear => {
+                let depth = ((position.y - bounds.y_min) / bounds.height()).clamp(0., 1.);
+                Vec2::new(tide * speed * (1. - 2. * depth), 0.)
+            }
+            CurrentKind::Vortex => {
+                // Stream function sin(a x) sin(b y), which has no flow
+                // through the walls; scaled so the fastest flow is 'speed'
+                let gyres = if params.boundary == BoundaryMode::PeriodicX {
+                    2.
+                } else {
+                    1.
+                };
+                let a = gyres * PI / bounds.width();
+                let b = PI / bounds.height();
+                let (x, y) = (position.x - bounds.x_min, position.y - bounds.y_min);
+                let scale = tide * speed / a.max(b);
+                Vec2::new(
+                    scale * b * (a * x).sin() * (b * y).cos(),
+                    -scale * a * (a * x).cos() * (b * y).sin(),
+                )
+            }
+            CurrentKind::CurlNoise => {
+                // Summed waves have an RMS speed of amplitude * sqrt(n / 2)
+                let amplitude = speed * (2. / self.waves.len().max(1) as f32).sqrt();
+                self.waves
+                    .iter()
+                    .map(|w| {
+                        let theta =
+                            w.wave_vector.dot(position) + w.phase + 2. * PI * w.drift * turns;
+                        let k = w.wave_vector.length().max(f32::EPSILON);
+                        amplitude * theta.cos() * Vec2::new(w.wave_vector.y, -w.wave_vector.x) / k
+                    })
+                    .sum()
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use crate::{Params, brain::Brain, color::Color, controller::ControllerKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Crossover {
+    Uniform,     // every gene comes from either parent with equal chance
+    SinglePoint, // genes before a random cut from one parent, after from the other
+}
+
+// Number of genes crossover works over (colour counts as r, g, b and the
+// controller with its brain as one)
+const NUM_GENES: usize = 17;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    /*
+     * The heritable part of a Creature. Offspring get a mutated copy of their
+     * parent's genome; everything else on a Creature is state that starts
+     * fresh at birth.
+     */
+    pub dexterity: f32,        // scales max speed and acceleration
+    pub hunger_threshold: f32, // below this hunger the creature looks for food
+    pub hunger_rate: f32,      // how quickly moving burns through hunger
+    pub color: Color,
+    pub mate_preference: f32, // largest genetic distance accepted in a mate
+    pub diet: f32,            // 0 = pure herbivore, 1 = pure carnivore
+    pub strength: f32,        // attack damage, costs hunger to maintain
+    pub boldness: f32,        // 0 = flees early, 1 = ignores predators
+    pub max_age: f32,         // age at which senescence sets in
+    pub adult_size: f32,      // size at which the creature is fully grown
+    pub vision_range: f32,    // how far the creature can see
+    pub field_of_view: f32,   // full angle of the vision cone around facing, up to 2 pi
+    pub smell: f32,           // sensitivity to food scent, costs hunger to maintain
+    pub density: f32,         // relative to water: sinkers pay to stay up, floaters drift up
+    pub controller: ControllerKind, // what decides where the creature goes
+    pub brain: Brain,         // network weights, only used by neural controllers
+}
+
+impl Genome {
+    pub fn mutate<R: Rng>(&self, rng: &mut R, params: &Params) -> Genome {
+        // Each gene independently mutates with probability mutation_rate; the
+        // Gaussian noise is relative to the gene's value so that genes on very
+        // different scales (e.g. hunger_rate ~ 1e-4) mutate comparably
+        Genome {
+            dexterity: mutate_gene(rng, self.dexterity, params).max(0.05),
+            hunger_threshold: mutate_gene(rng, self.hunger_threshold, params).clamp(1., 100.),
+            hunger_rate: mutate_gene(rng, self.hunger_rate, params).max(1e-6),
+            color: Color::new(
+                mutate_unit(rng, self.color.r, params),
+                mutate_unit(rng, self.color.g, params),
+                mutate_unit(rng, self.color.b, params),
+                self.color.a,
+            ),
+            mate_preference: mutate_gene(rng, self.mate_preference, params).clamp(0.01, 1.),
+            diet: mutate_unit(rng, self.diet, params),
+            strength: mutate_gene(rng, self.strength, params).max(0.05),
+            boldness: mutate_unit(rng, self.boldness, params),
+            max_age: mutate_gene(rng, self.max_age, params).max(1.),
+            adult_size: mutate_gene(rng, self.adult_size, params).max(0.1),
+            vision_range: mutate_gene(rng, self.vision_range, params).max(1.),
+            field_of_view: mutate_gene(rng, self.field_of_view, params).clamp(0.01, 2. * PI),
+            smell: mutate_gene(rng, self.smell, params).max(0.01),
+            density: mutate_gene(rng, self.density, params).max(0.1),
+            controller: self.controller,
+            // Unused brains are left alone rather than drifting for nothing
+            brain: match self.controller {
+                ControllerKind::Neural => self.brain.mutate(rng, params),
+                ControllerKind::HandCoded => self.brain,
+            },
+        }
+    }
+
+    pub fn distance(&self, other: &Genome) -> f32 {
+        // Mean relative difference over the genes, so it is roughly in [0, 1];
+        // the controller and brain don't count towards it
+        let rel = |a: f32, b: f32| (a - b).abs() / a.abs().max(b.abs()).max(f32::EPSILON);
+        let color = ((self.color.r - other.color.r).abs()
+            + (self.color.g - other.color.g).abs()
+            + (self.color.b - other.color.b).abs())
+            / 3.;
+        (rel(self.dexterity, other.dexterity)
+            + rel(self.hunger_threshold, other.hunger_threshold)
+            + rel(self.hunger_rate, other.hunger_rate)
+            + rel(self.mate_preference, other.mate_preference)
+            + (self.diet - other.diet).abs()
+            + rel(self.strength, other.strength)
+            + (self.boldness - other.boldness).abs()
+            + rel(self.max_age, other.max_age)
+            + rel(self.adult_size, other.adult_size)
+            + rel(self.vision_range, other.vision_range)
+            + rel(self.field_of_view, other.field_of_view)
+            + rel(self.smell, other.smell)
+            + rel(self.density, other.density)
+            + color)
+            / 14.
+    }
+
+    pub fn accepts(&self, other: &Genome) -> bool {
+        self.distance(other) <= self.mate_preference
+    }
+
+    pub fn crossover<R: Rng>(&self, other: &Genome, rng: &mut R, method: Crossover) -> Genome {
+        // Genes are picked in declaration order so that a single-point cut
+        // keeps neighbouring genes together
+        let cut = rng.random_range(1..NUM_GENES);
+        let mut gene = 0;
+        let mut from_self = || {
+            let from_self = match method {
+                Crossover::Uniform => rng.random::<bool>(),
+                Crossover::SinglePoint => gene < cut,
+            };
+            gene += 1;
+            from_self
+        };
+        let mut pick = |a: f32, b: f32| if from_self() { a } else { b };
+        let mut genome = Genome {
+            dexterity: pick(self.dexterity, other.dexterity),
+            hunger_threshold: pick(self.hunger_threshold, other.hunger_threshold),
+            hunger_rate: pick(self.hunger_rate, other.hunger_rate),
+            color: Color::new(
+                pick(self.color.r, other.color.r),
+                pick(self.color.g, other.color.g),
+                pick(self.color.b, other.color.b),
+                self.color.a,
+            ),
+            mate_preference: pick(self.mate_preference, other.mate_preference),
+            diet: pick(self.diet, other.diet),
+            strength: pick(self.strength, other.strength),
+            boldness: pick(self.boldness, other.boldness),
+            max_age: pick(self.max_age, other.max_age),
+            adult_size: pick(self.adult_size, other.adult_size),
+            vision_range: pick(self.vision_range, other.vision_range),
+            field_of_view: pick(self.field_of_view, other.field_of_view),
+            smell: pick(self.smell, other.smell),
+            density: pick(self.density, other.density),
+            controller: self.controller,
+            brain: self.brain,
+        };
+        // A brain is only any use together with the controller that runs it
+        if !from_self() {
+            genome.controller = other.controller;
+            genome.brain = other.brain;
+        }
+        genome
+    }
+}
+
+fn mutate_gene<R: Rng>(rng: &mut R, value: f32, params: &Params) -> f32 {
+    if rng.random::<f32>() >= params.mutation_rate {
+        return value;
+    }
+    let noise: f32 = rng.sample(StandardNormal);
+    value * (1. + params.mutation_sigma * noise)
+}
+
+fn mutate_unit<R: Rng>(rng: &mut R, value: f32, params: &Params) -> f32 {
+    // Genes living in [0, 1] (colour channels, diet, boldness) drift additively so that
+    // values at zero can still change
+    if rng.random::<f32>() >= params.mutation_rate {
+        return value;
+    }
+    let noise: f32 = rng.sample(StandardNormal);
+    (value + params.mutation_sigma * noise).clamp(0., 1.)
+}
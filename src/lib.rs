@@ -1,7 +1,39 @@
-use std::{collections::HashMap, f32::consts::PI};
+use std::{collections::BTreeMap, f32::consts::PI};
 
-use ::rand::{Rng, rngs::ThreadRng};
-use macroquad::prelude::*;
+use glam::{Vec2, vec2};
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub mod brain;
+pub mod cli;
+pub mod color;
+pub mod config;
+pub mod controller;
+pub mod current;
+pub mod genome;
+pub mod lotka_volterra;
+pub mod phylogeny;
+pub mod recorder;
+pub mod scent;
+pub mod snapshot;
+pub mod spatial;
+use brain::*;
+use color::*;
+use config::*;
+use controller::*;
+use current::*;
+use genome::*;
+use phylogeny::*;
+use scent::*;
+use spatial::*;
+
+// The simulation RNG: every random draw in the simulation goes through one of
+// these so that a seed (plus Params) fully determines a run
+pub type SimRng = rand_chacha::ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
 
 // Traits
 pub trait HasPosition {
@@ -9,18 +41,41 @@ pub trait HasPosition {
 }
 
 // Enums
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Starvation,
+    Predation,
+    OldAge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BoundaryMode {
+    Reflective, // creatures bounce off the edges
+    Repulsive,  // creatures are pushed back from within 'padding' of the edges
+    PeriodicX,  // left and right edges wrap around, top and bottom repel
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LifeStage {
+    Juvenile, // still growing, cannot reproduce
+    Adult,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Target {
-    Food(usize),
-    Creature(usize),
+    Food(usize),     // a PlantSource
+    Meat(usize),     // a MeatSource
+    Creature(usize), // prey being hunted
+    Mate(usize),     // a creature to reproduce with
     Position(Vec2),
 }
 
 impl Target {
     pub fn position(&self, world: &World) -> Option<Vec2> {
         match self {
-            Target::Creature(id) => world.creatures.get(id).map(|c| c.position),
+            Target::Creature(id) | Target::Mate(id) => world.creatures.get(id).map(|c| c.position),
             Target::Food(id) => world.plant_sources.get(id).map(|f| f.position),
+            Target::Meat(id) => world.meat_sources.get(id).map(|m| m.position),
             Target::Position(pos) => Some(*pos),
         }
     }
@@ -28,13 +83,23 @@ impl Target {
 
 // Structs
 
+#[derive(Serialize, Deserialize)]
 pub struct World {
-    pub next_id: usize, // every entity has an ID, the ID space is shared
-    pub creatures: HashMap<usize, Creature>, // all creatures
-    pub plant_sources: HashMap<usize, PlantSource>, // all plant sources
-    pub meat_sources: HashMap<usize, MeatSource>, // all meat food sources
-    pub params: Params, // simulation params
-    pub bounds: Bounds, // world boundaries
+    // every entity has an ID, the ID space is shared; maps are ordered so that
+    // iteration (and therefore RNG consumption) is identical between runs
+    pub next_id: usize,
+    pub creatures: BTreeMap<usize, Creature>, // all creatures
+    pub plant_sources: BTreeMap<usize, PlantSource>, // all plant sources
+    pub meat_sources: BTreeMap<usize, MeatSource>, // all meat food sources
+    pub params: Params,                       // simulation params
+    pub bounds: Bounds,                       // world boundaries
+    pub stats: Stats,                         // running counters for analysis
+    pub phylogeny: Phylogeny,                 // ancestry of every creature born
+    pub scent: ScentField,                    // food scent creatures can follow
+    pub current: CurrentField,                // water flow that carries everything along
+    pub steps: usize,                         // update_world calls since the world began
+    #[serde(skip)]
+    pub grid: SpatialGrid,  // spatial index, rebuilt at the start of every step and after loading
 }
 
 impl World {
@@ -47,11 +112,21 @@ impl World {
     ) -> Self {
         let mut world = World {
             next_id: 0,
-            creatures: HashMap::new(),
-            plant_sources: HashMap::new(),
-            meat_sources: HashMap::new(),
+            creatures: BTreeMap::new(),
+            plant_sources: BTreeMap::new(),
+            meat_sources: BTreeMap::new(),
             params,
             bounds,
+            stats: Stats::default(),
+            phylogeny: Phylogeny::default(),
+            scent: ScentField::new(
+                &bounds,
+                params.scent_cell_size,
+                params.boundary == BoundaryMode::PeriodicX,
+            ),
+            current: CurrentField::new(&params, &bounds),
+            steps: 0,
+            grid: SpatialGrid::default(),
         };
 
         for creature in creatures {
@@ -66,12 +141,19 @@ impl World {
             world.add_meat_source(meat_source);
         }
 
+        world.reset_grid();
         world
     }
     pub fn add_creature(&mut self, creature: Creature) -> usize {
+        // A creature without known parents, e.g. a founder
+        self.add_offspring(creature, [None, None])
+    }
+
+    pub fn add_offspring(&mut self, creature: Creature, parents: [Option<usize>; 2]) -> usize {
         let id = self.next_id;
         self.next_id += 1;
         self.creatures.insert(id, creature);
+        self.phylogeny.record_birth(id, parents, self.params.time);
         id
     }
 
@@ -88,9 +170,153 @@ impl World {
         self.meat_sources.insert(id, meat_source);
         id
     }
+
+    pub fn reset_grid(&mut self) {
+        // Size a fresh spatial index for the current bounds and params, e.g.
+        // after loading a snapshot (the grid itself is never saved)
+        self.grid = SpatialGrid::new(
+            &self.bounds,
+            self.params.grid_cell_size,
+            self.params.boundary == BoundaryMode::PeriodicX,
+        );
+        self.rebuild_grid();
+    }
+
+    pub fn rebuild_grid(&mut self) {
+        self.grid.clear();
+        for (id, creature) in &self.creatures {
+            self.grid
+                .insert(EntityKind::Creature, *id, creature.position);
+        }
+        for (id, plant) in &self.plant_sources {
+            self.grid.insert(EntityKind::Plant, *id, plant.position);
+        }
+        for (id, meat) in &self.meat_sources {
+            self.grid.insert(EntityKind::Meat, *id, meat.position);
+        }
+    }
+
+    pub fn light(&self, y: f32) -> f32 {
+        // Light at height y, the top of the world being the surface
+        self.params.light(y - self.bounds.y_min)
+    }
+
+    pub fn current_at(&self, position: Vec2) -> Vec2 {
+        // Velocity of the water at 'position' right now
+        self.current.velocity(&self.params, &self.bounds, position)
+    }
+
+    pub fn displacement(&self, from: Vec2, to: Vec2) -> Vec2 {
+        self.bounds.displacement(from, to, self.params.boundary)
+    }
+
+    pub fn wrap_position(&self, v: Vec2) -> Vec2 {
+        self.bounds.wrap(v, self.params.boundary)
+    }
+
+    fn entity_position(&self, kind: EntityKind, id: usize) -> Option<Vec2> {
+        match kind {
+            EntityKind::Creature => self.creatures.get(&id).map(|c| c.position),
+            EntityKind::Plant => self.plant_sources.get(&id).map(|p| p.position),
+            EntityKind::Meat => self.meat_sources.get(&id).map(|m| m.position),
+        }
+    }
+
+    pub fn nearest_visible(
+        &self,
+        viewer: &Creature,
+        kind: EntityKind,
+        max_radius: f32,
+        mut accept: impl FnMut(usize) -> bool,
+    ) -> Option<(usize, f32)> {
+        // Like nearest, but only considering what 'viewer' can see
+        let radius = max_radius.min(viewer.genome.vision_range);
+        self.nearest(viewer.position, kind, radius, |id| {
+            self.entity_position(kind, id)
+                .is_some_and(|position| viewer.sees(position, self))
+                && accept(id)
+        })
+    }
+
+    pub fn entities_within(&self, position: Vec2, radius: f32, kind: EntityKind) -> Vec<usize> {
+        // IDs of all entities of a kind within 'radius' of 'position'
+        let mut ids = Vec::new();
+        self.grid.for_each_near(kind, position, radius, |id| {
+            if let Some(other) = self.entity_position(kind, id)
+                && self.displacement(position, other).length() <= radius
+            {
+                ids.push(id);
+            }
+        });
+        ids
+    }
+
+    pub fn nearest(
+        &self,
+        position: Vec2,
+        kind: EntityKind,
+        max_radius: f32,
+        mut accept: impl FnMut(usize) -> bool,
+    ) -> Option<(usize, f32)> {
+        // Closest entity of a kind within max_radius that 'accept' agrees to,
+        // with its distance. Searches rings of grid cells outwards and stops
+        // once no unvisited cell can hold anything closer
+        let center = self.grid.cell_coords(position);
+        let mut nearest: Option<(usize, f32)> = None;
+        for ring in 0..=self.grid.max_ring() {
+            // Anything in this ring or further out is at least this far away
+            let reach = ring.saturating_sub(1) as f32 * self.grid.cell_size();
+            if reach > max_radius || nearest.is_some_and(|(_, d)| d <= reach) {
+                break;
+            }
+            self.grid.for_each_in_ring(kind, center, ring, |id| {
+                let Some(other) = self.entity_position(kind, id) else {
+                    return;
+                };
+                let distance = self.displacement(position, other).length();
+                if distance <= max_radius && nearest.is_none_or(|(_, d)| distance < d) && accept(id)
+                {
+                    nearest = Some((id, distance));
+                }
+            });
+        }
+        nearest
+    }
+
+    pub fn nearest_plant(&self, position: Vec2) -> Option<usize> {
+        self.nearest(position, EntityKind::Plant, f32::MAX, |_| true)
+            .map(|(id, _)| id)
+    }
+
+    pub fn kill_creature(&mut self, id: usize, cause: DeathCause) -> Option<usize> {
+        // Remove a creature and leave its body behind as a MeatSource,
+        // returning the ID of the new meat
+        let creature = self.creatures.remove(&id)?;
+        self.phylogeny.record_death(id, self.params.time);
+        match cause {
+            DeathCause::Starvation => self.stats.starvation_deaths += 1,
+            DeathCause::Predation => self.stats.predation_deaths += 1,
+            DeathCause::OldAge => self.stats.old_age_deaths += 1,
+        }
+        let amount = self.params.meat_per_body_mass * creature.body_mass();
+        Some(self.add_meat_source(MeatSource::new(
+            creature.position,
+            amount,
+            creature.genome.density,
+        )))
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub sexual_births: usize,
+    pub asexual_births: usize,
+    pub starvation_deaths: usize,
+    pub predation_deaths: usize,
+    pub old_age_deaths: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlantSource {
     /*
      * A PlantSource is a place where creatures flock to
@@ -102,12 +328,19 @@ pub struct PlantSource {
     pub velocity: Vec2,
     pub max_amount: f32,
     pub amount: f32,
-    pub regrow_freq: f32, // how likely the plant is to spread each timestep (percent chance)
-    pub regrow_amount: f32, // how much the plant regrows in a timestep
+    pub regrow_freq: f32, // how likely the plant is to spread each timestep in full light
+    pub regrow_amount: f32, // how much the plant regrows per unit time in full light
+    pub density: f32,     // relative to water, lighter plants float up
+}
+
+impl HasPosition for PlantSource {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
 }
 
 impl PlantSource {
-    pub fn new_rand(rng: &mut ThreadRng, bounds: &Bounds) -> Self {
+    pub fn new_rand<R: Rng>(rng: &mut R, bounds: &Bounds) -> Self {
         let max_amount = rng.random_range(50.0..100.0);
         Self {
             position: rvec2_range(rng, bounds),
@@ -116,62 +349,138 @@ impl PlantSource {
             amount: max_amount,
             regrow_freq: rng.random_range(1e-5..5e-5),
             regrow_amount: rng.random_range(0.0..1.0),
+            density: rng.random_range(0.97..1.05),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MeatSource {
     /*
      * A MeatSource is a place where meat-eating creatures flock to if they are
      * hungry. The amount decreseas when creatures eat, does not regrow, but
-     * dead creatures turn into MeatSources.
-     * TODO: implement decay?
+     * dead creatures turn into MeatSources. Meat decays exponentially
+     * (Params::meat_half_life) and is removed once almost nothing is left.
      */
     pub position: Vec2,
     pub velocity: Vec2,
     pub max_amount: f32,
     pub amount: f32,
+    pub density: f32, // relative to water, that of the creature it came from
+}
+
+impl HasPosition for MeatSource {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
 }
 
 impl MeatSource {
-    pub fn new_rand(rng: &mut ThreadRng, bounds: &Bounds) -> Self {
+    pub fn new(position: Vec2, amount: f32, density: f32) -> Self {
+        Self {
+            position,
+            velocity: Vec2::ZERO,
+            max_amount: amount,
+            amount,
+            density,
+        }
+    }
+
+    pub fn new_rand<R: Rng>(rng: &mut R, bounds: &Bounds) -> Self {
         let max_amount = rng.random_range(50.0..100.0);
         Self {
             position: rvec2_range(rng, bounds),
             velocity: Vec2::ZERO,
             max_amount,
             amount: max_amount,
+            density: rng.random_range(1.0..1.2),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Creature {
     pub position: Vec2, // position in worldspace
     pub velocity: Vec2, // velocity in px space
-    // pub size: f32,      // creature size (for rendering and life stage)
-    // pub age: f32,       // age affects other attributes, past a threshold, older creatures are more
-    // likely to die, and creatures must be a certain age before reproducing
-    // pub strength: f32,
-    pub dexterity: f32,
-    pub facing: f32, // facing angle in radians
+    pub genome: Genome, // heritable traits
+    pub facing: f32,    // facing angle in radians
+    pub size: f32,      // grows with food from BIRTH_SIZE up to genome.adult_size
+    pub age: f32,       // past genome.max_age creatures become more and more likely to die
     pub hunger: f32,
-    pub hunger_threshold: f32,
-    pub hunger_rate: f32,
-    // TODO: Add different shapes for evolutionary stuff, create a "size" for
-    // visual clarity on how beeg the creature is
-    // Also need to add HP for combat
-    pub color: Color,
+    pub hp: f32,                    // health, the creature is killed when it reaches 0
+    pub kills: usize,               // creatures this one has killed
+    pub reproduction_cooldown: f32, // time left before the creature can reproduce again
+    pub mate_search_time: f32,      // time spent looking for a mate so far
+    // TODO: Add different shapes for evolutionary stuff
     pub movement_target: Option<Target>,
 }
 
+impl HasPosition for Creature {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+}
+
+// Newborns start at this fraction of their adult size
+const BIRTH_SIZE: f32 = 0.25;
+// Size gained per unit of digested food while growing
+const GROWTH_PER_FOOD: f32 = 0.05;
+
 impl Creature {
+    pub fn new(position: Vec2, genome: Genome, hunger: f32) -> Self {
+        // A newborn: small, age zero and at full (size-dependent) health
+        let mut creature = Self {
+            position,
+            velocity: Vec2::ZERO,
+            genome,
+            facing: 0.,
+            size: BIRTH_SIZE * genome.adult_size,
+            age: 0.,
+            hunger,
+            hp: 0.,
+            kills: 0,
+            reproduction_cooldown: 0.,
+            mate_search_time: 0.,
+            movement_target: None,
+        };
+        creature.hp = creature.max_hp();
+        creature
+    }
+
     fn is_hungry(&self) -> bool {
-        self.hunger <= self.hunger_threshold
+        self.hunger <= self.genome.hunger_threshold
     }
 
-    fn distance_to_food(&self, food: &PlantSource) -> f32 {
-        self.position.distance(food.position)
+    fn body_mass(&self) -> f32 {
+        // Size times how muscular (dexterity and strength) the body is
+        self.size * 0.5 * (self.genome.dexterity + self.genome.strength)
+    }
+
+    pub fn life_stage(&self) -> LifeStage {
+        if self.size >= self.genome.adult_size {
+            LifeStage::Adult
+        } else {
+            LifeStage::Juvenile
+        }
+    }
+
+    pub fn max_hp(&self) -> f32 {
+        100. * self.body_mass()
+    }
+
+    fn ready_to_reproduce(&self, params: &Params) -> bool {
+        self.life_stage() == LifeStage::Adult
+            && self.hunger >= params.reproduction_hunger
+            && self.reproduction_cooldown <= 0.
+    }
+
+    fn plant_efficiency(&self) -> f32 {
+        // Fraction of a plant's nutrition this creature can digest
+        1. - self.genome.diet
+    }
+
+    fn meat_efficiency(&self) -> f32 {
+        self.genome.diet
     }
 
     fn square_speed(&self) -> f32 {
@@ -179,11 +488,45 @@ impl Creature {
     }
 
     fn max_speed(&self) -> f32 {
-        self.dexterity * 10.
+        self.genome.dexterity * 10.
     }
 
     fn acceleration(&self) -> f32 {
-        self.dexterity
+        self.genome.dexterity
+    }
+
+    pub fn sees(&self, position: Vec2, world: &World) -> bool {
+        // Whether 'position' is inside the creature's vision cone
+        let to = world.displacement(self.position, position);
+        let distance = to.length();
+        if distance > self.genome.vision_range {
+            return false;
+        }
+        let half_angle = 0.5 * self.genome.field_of_view;
+        half_angle >= PI
+            || distance == 0.
+            || Vec2::from_angle(self.facing).dot(to / distance) >= half_angle.cos()
+    }
+
+    pub fn visual_field(&self) -> f32 {
+        // Area of the vision cone, which is what senses cost to maintain
+        0.5 * self.genome.field_of_view * self.genome.vision_range.powi(2)
+    }
+
+    fn smell_food(&self, world: &World) -> Option<Vec2> {
+        // Uphill direction of the scent of food the creature can digest
+        // (each kind weighted by how well it digests it), if the scent is
+        // strong enough for it to notice
+        let scent = &world.scent;
+        let (plant, meat) = (self.plant_efficiency(), self.meat_efficiency());
+        let strength = plant * scent.sample(EntityKind::Plant, self.position)
+            + meat * scent.sample(EntityKind::Meat, self.position);
+        if self.genome.smell * strength < world.params.scent_threshold {
+            return None;
+        }
+        (plant * scent.gradient(EntityKind::Plant, self.position)
+            + meat * scent.gradient(EntityKind::Meat, self.position))
+        .try_normalize()
     }
 
     fn update_facing(&mut self) {
@@ -203,7 +546,7 @@ impl Creature {
         if let Some(target) = self.movement_target {
             // Second unwrap: if Target ID still exists in the world
             if let Some(target_pos) = target.position(world) {
-                to_target = target_pos - self.position;
+                to_target = world.displacement(self.position, target_pos);
                 squared_distance = to_target.length_squared();
             } else {
                 // No target, should actually remove the target here?
@@ -225,27 +568,47 @@ impl Creature {
         let desired_speed = self.max_speed() * speed_factor;
 
         let desired_velocity = to_target.normalize() * desired_speed;
+        self.steer(desired_velocity, world);
+        false
+    }
 
-        let steering = desired_velocity - self.velocity;
-        let steering_clamped = steering.clamp_length_max(self.acceleration());
+    fn flee(&mut self, away: Vec2, world: &World) {
+        // Counterpart to move_to_target: steer at full speed along 'away'
+        self.steer(away.normalize_or_zero() * self.max_speed(), world);
+    }
 
-        self.velocity += steering_clamped;
+    fn steer(&mut self, desired_velocity: Vec2, world: &World) {
+        // Turn the velocity towards desired_velocity as fast as the
+        // creature's acceleration allows, then move; all controllers go
+        // through here
+        let steering = desired_velocity - self.velocity;
+        self.velocity += steering.clamp_length_max(self.acceleration());
         self.velocity = self.velocity.clamp_length_max(self.max_speed());
 
         self.position += self.velocity * world.params.timestep;
-        return false;
     }
 
-    fn handle_reached_target(&mut self, world: &mut World) {
+    fn handle_reached_target<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        world: &mut World,
+    ) -> Option<(Creature, usize)> {
         // Only called when we have reached the target (within the threshold),
-        // do different things depending on what the target is
+        // do different things depending on what the target is; returns any
+        // offspring produced and the mate it was had with
         match self.movement_target {
-            // Do nothing on the creature right now, later predators will attack
-            Some(Target::Creature(id)) => (),
+            // Attack the prey
+            Some(Target::Creature(id)) => self.attack(id, world),
+            // Mate with the creature
+            Some(Target::Mate(id)) => {
+                self.movement_target = None;
+                return self.mate_with(rng, id, world).map(|child| (child, id));
+            }
             // Eat food
             Some(Target::Food(id)) => {
                 if let Some(food) = world.plant_sources.get_mut(&id) {
-                    self.eat_food(food, world.params.timestep);
+                    let nutrition = world.params.plant_nutrition * self.plant_efficiency();
+                    self.eat_food(&mut food.amount, nutrition, world.params.timestep);
                     if food.amount <= 0.0 {
                         // Remove the food source and the target
                         self.movement_target = None;
@@ -257,27 +620,117 @@ impl Creature {
                     self.movement_target = None;
                 }
             }
-            Some(Target::Position(pos)) => self.movement_target = None,
+            // Scavenge meat
+            Some(Target::Meat(id)) => {
+                if let Some(meat) = world.meat_sources.get_mut(&id) {
+                    let nutrition = world.params.meat_nutrition * self.meat_efficiency();
+                    self.eat_food(&mut meat.amount, nutrition, world.params.timestep);
+                    if meat.amount <= 0.0 {
+                        self.movement_target = None;
+                        world.meat_sources.remove(&id);
+                    }
+                } else {
+                    self.movement_target = None;
+                }
+            }
+            Some(Target::Position(_pos)) => self.movement_target = None,
             None => (),
         };
+        None
+    }
+
+    fn mate_with<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        mate_id: usize,
+        world: &mut World,
+    ) -> Option<Creature> {
+        // Sexual reproduction: both parents must still be ready and accept
+        // each other; each pays half the cost of the offspring, whose genome
+        // is a mutated crossover of both
+        let params = world.params;
+        let bounds = world.bounds;
+        let mate = world.creatures.get_mut(&mate_id)?;
+        if !(self.ready_to_reproduce(&params)
+            && mate.ready_to_reproduce(&params)
+            && self.genome.accepts(&mate.genome)
+            && mate.genome.accepts(&self.genome))
+        {
+            return None;
+        }
+        let genome = self
+            .genome
+            .crossover(&mate.genome, rng, params.crossover)
+            .mutate(rng, &params);
+        for parent in [&mut *self, &mut *mate] {
+            parent.hunger -= 0.5 * params.reproduction_cost;
+            parent.reproduction_cooldown = params.reproduction_cooldown;
+            parent.mate_search_time = 0.;
+        }
+        if matches!(mate.movement_target, Some(Target::Mate(_))) {
+            mate.movement_target = None;
+        }
+        let mut position = self.position
+            + 0.5 * bounds.displacement(self.position, mate.position, params.boundary);
+        clamp_to_world_bounds(&mut position, &bounds, &params);
+        world.stats.sexual_births += 1;
+        Some(Creature::new(position, genome, params.reproduction_cost))
+    }
+
+    pub fn is_predator(&self, params: &Params) -> bool {
+        // Carnivorous enough to hunt other creatures
+        self.genome.diet >= params.hunt_min_diet
+    }
+
+    fn can_hunt(&self, prey: &Creature, params: &Params) -> bool {
+        // Only predators hunt, and only prey that is not too big for them to
+        // take down
+        self.is_predator(params)
+            && prey.body_mass() <= params.max_prey_mass_ratio * self.body_mass()
+    }
+
+    fn attack(&mut self, prey_id: usize, world: &mut World) {
+        // Damage the prey while in contact; a kill leaves meat behind, which
+        // becomes the new target so the predator can eat its catch
+        let params = world.params;
+        let Some(prey) = world.creatures.get_mut(&prey_id) else {
+            self.movement_target = None;
+            return;
+        };
+        prey.hp -= params.attack_damage * self.genome.strength * params.timestep;
+        if prey.hp > 0. {
+            return;
+        }
+        self.kills += 1;
+        self.movement_target = world
+            .kill_creature(prey_id, DeathCause::Predation)
+            .map(Target::Meat);
     }
 
-    fn eat_food(&mut self, food: &mut PlantSource, timestep: f32) {
-        // Eat a fixed 5*dt units of food
+    fn eat_food(&mut self, food_amount: &mut f32, nutrition: f32, timestep: f32) {
+        // Eat a fixed 5*dt units of food, of which only 'nutrition' per unit
+        // (food value times diet-dependent efficiency) is digested
         let food_eaten = 5.0 * timestep;
-        self.hunger = (self.hunger + food_eaten).clamp(0., 100.);
+        self.hunger = (self.hunger + food_eaten * nutrition).clamp(0., 100.);
+        // Juveniles grow with what they digest
+        self.size =
+            (self.size + GROWTH_PER_FOOD * food_eaten * nutrition).min(self.genome.adult_size);
         // Stop eating if we are full
-        if self.hunger >= (self.hunger_threshold * 1.5).min(100.) {
+        if self.hunger >= (self.genome.hunger_threshold * 1.5).min(100.) {
             self.movement_target = None;
         }
-        food.amount -= food_eaten;
+        *food_amount -= food_eaten;
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Params {
-    pub window_width: f32,
+    pub seed: u64,         // seed for the simulation RNG, see `seeded_rng`
+    pub window_width: f32, // initial viewer window size
     pub window_height: f32,
+    pub world_width: f32, // size of the world, independent of the window
+    pub world_height: f32,
     pub padding: f32,
     pub time: f32,
     // regrow a random amount of food when freq > timer, reset timer
@@ -285,29 +738,163 @@ pub struct Params {
     pub plant_regrow_timer: f32,
     pub plant_regrow_freq: f32,
     pub timestep: f32,
+    // everything denser than water sinks under gravity less buoyancy and
+    // lighter things float up, while drag keeps a 'damping' fraction of
+    // their velocity through the water per unit time. Food settles at a
+    // terminal velocity of Params::net_gravity / -ln(damping); creatures
+    // denser than water pay hover_cost hunger per unit time and body mass
+    // for each unit of net gravity they hold their depth against
     pub damping: f32,
-    pub food_terminal_velocity: f32,
     pub gravity: f32,
+    pub hover_cost: f32,
+    // reproduction: a creature at or above reproduction_hunger spends
+    // reproduction_cost hunger on one offspring, which starts with that much
+    pub reproduction_hunger: f32,
+    pub reproduction_cost: f32,
+    pub reproduction_cooldown: f32, // time between births for each parent
+    // a ready creature looks for a mate within mate_search_radius for up to
+    // mate_search_time before falling back to cloning itself
+    pub mate_search_radius: f32,
+    pub mate_search_time: f32,
+    pub crossover: Crossover,
+    // hunger gained per unit eaten by a creature with full efficiency for the
+    // food type (efficiency is 1 - diet for plants and diet for meat)
+    pub plant_nutrition: f32,
+    pub meat_nutrition: f32,
+    // hunting: creatures with diet >= hunt_min_diet hunt prey up to
+    // max_prey_mass_ratio times their body mass, dealing attack_damage *
+    // strength damage per unit time while in contact
+    pub hunt_min_diet: f32,
+    pub hunt_cost: f32, // distance weighting of prey relative to meat sources
+    // prey flees from would-be predators within threat_radius * (1 - boldness)
+    pub threat_radius: f32,
+    pub grid_cell_size: f32, // cell size of the spatial index used for searches
+    pub boundary: BoundaryMode,
+    pub hp_regen: f32, // HP regained per unit time while not hungry
+    // death chance per unit time at twice max_age (scales linearly from max_age)
+    pub senescence_rate: f32,
+    pub max_prey_mass_ratio: f32,
+    pub attack_damage: f32,
+    pub meat_per_body_mass: f32, // meat left behind by a dead creature per unit body mass
+    // meat decays with this half-life and is removed below meat_min_amount; a
+    // meat_decay_to_plants fraction of the decayed mass feeds the nearest
+    // plant within meat_fertilise_radius (0 disables this)
+    pub meat_half_life: f32,
+    pub meat_min_amount: f32,
+    pub meat_decay_to_plants: f32,
+    pub meat_fertilise_radius: f32,
+    // plants photosynthesise with the light reaching their depth, which falls
+    // off by a factor e every 1 / light_attenuation px (0 lights the whole
+    // world evenly). Below the light_compensation light level upkeep
+    // outweighs growth and they shrink
+    pub light_attenuation: f32,
+    pub light_compensation: f32,
+    // water current carrying food and creatures along, see CurrentKind;
+    // current_direction (radians) only applies to a constant current and
+    // current_scale (px) to curl noise. A current_period makes it vary in
+    // time (0 keeps it steady). Plant offspring drift with the current for
+    // plant_dispersal_time before settling
+    pub current: CurrentKind,
+    pub current_speed: f32,
+    pub current_direction: f32,
+    pub current_scale: f32,
+    pub current_period: f32,
+    pub plant_dispersal_time: f32,
+    // how often the phylogeny forgets extinct lineages (0 keeps everything)
+    pub phylogeny_prune_interval: f32,
+    // hunger per unit time for each 10^4 px^2 of visual field
+    pub vision_cost: f32,
+    // food scent: every unit of food emits scent_emission per unit time into
+    // a grid of scent_cell_size cells, where it diffuses and decays at the
+    // given rates. A creature smells food where smell * concentration
+    // reaches scent_threshold, and its sense of smell costs smell_cost
+    // hunger per unit time per unit of smell
+    pub scent_cell_size: f32,
+    pub scent_emission: f32,
+    pub scent_diffusion: f32,
+    pub scent_decay: f32,
+    pub scent_threshold: f32,
+    pub smell_cost: f32,
+    // per-gene chance to mutate and relative std dev of the Gaussian noise
+    pub mutation_rate: f32,
+    pub mutation_sigma: f32,
+}
+
+impl Params {
+    pub fn net_gravity(&self, density: f32) -> f32 {
+        // Acceleration of something of 'density' (relative to water) under
+        // gravity less buoyancy; negative floats upwards
+        self.gravity * (1. - 1. / density)
+    }
+
+    pub fn light(&self, depth: f32) -> f32 {
+        // Fraction of surface light reaching 'depth', falling off
+        // exponentially (Beer-Lambert); above the surface is full light
+        (-self.light_attenuation * depth.max(0.)).exp()
+    }
 }
 
 impl Default for Params {
     fn default() -> Params {
         Params {
+            seed: 0,
             window_width: 1600.,
             window_height: 1200.,
+            world_width: 1600.,
+            world_height: 1200.,
             padding: 20.,
             time: 0.,
             plant_regrow_timer: 0.,
             plant_regrow_freq: 50.,
             timestep: 1e-2,
-            gravity: 1e-2,
-            food_terminal_velocity: 10.,
+            gravity: 10.,
             damping: 0.9,
+            hover_cost: 2e-2,
+            reproduction_hunger: 80.,
+            reproduction_cost: 40.,
+            reproduction_cooldown: 20.,
+            mate_search_radius: 200.,
+            mate_search_time: 10.,
+            crossover: Crossover::Uniform,
+            plant_nutrition: 1.,
+            meat_nutrition: 2.,
+            hunt_min_diet: 0.5,
+            hunt_cost: 2.,
+            threat_radius: 150.,
+            grid_cell_size: 50.,
+            boundary: BoundaryMode::Repulsive,
+            hp_regen: 5.,
+            senescence_rate: 1.,
+            max_prey_mass_ratio: 1.5,
+            attack_damage: 50.,
+            meat_per_body_mass: 50.,
+            meat_half_life: 200.,
+            meat_min_amount: 1.,
+            meat_decay_to_plants: 0.5,
+            meat_fertilise_radius: 100.,
+            light_attenuation: 0.,
+            light_compensation: 0.,
+            current: CurrentKind::None,
+            current_speed: 3.,
+            current_direction: 0.,
+            current_scale: 400.,
+            current_period: 0.,
+            plant_dispersal_time: 5.,
+            phylogeny_prune_interval: 0.,
+            vision_cost: 2e-3,
+            scent_cell_size: 25.,
+            scent_emission: 1.,
+            scent_diffusion: 2000.,
+            scent_decay: 0.2,
+            scent_threshold: 0.5,
+            smell_cost: 5e-3,
+            mutation_rate: 0.1,
+            mutation_sigma: 0.1,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Bounds {
     pub x_min: f32,
     pub x_max: f32,
@@ -315,13 +902,43 @@ pub struct Bounds {
     pub y_max: f32,
 }
 
+impl Bounds {
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    pub fn displacement(&self, from: Vec2, to: Vec2, boundary: BoundaryMode) -> Vec2 {
+        // Shortest vector from 'from' to 'to', across the seam if x is periodic
+        let mut d = to - from;
+        if boundary == BoundaryMode::PeriodicX {
+            d.x -= self.width() * (d.x / self.width()).round();
+        }
+        d
+    }
+
+    pub fn wrap(&self, v: Vec2, boundary: BoundaryMode) -> Vec2 {
+        // Bring x back into [x_min, x_max) if it is periodic
+        match boundary {
+            BoundaryMode::PeriodicX => vec2(
+                self.x_min + (v.x - self.x_min).rem_euclid(self.width()),
+                v.y,
+            ),
+            _ => v,
+        }
+    }
+}
+
 // Numerics (mainly scaling vectors)
 pub fn range_scale(v: f32, old_lo: f32, old_hi: f32, new_lo: f32, new_hi: f32) -> f32 {
     // Scale a value 'v' from [old_lo, old_hi] to [new_lo, new_hi]
     new_lo + v * (new_hi - new_lo) / (old_hi - old_lo)
 }
 
-pub fn rvec2_range(rng: &mut ThreadRng, bounds: &Bounds) -> Vec2 {
+pub fn rvec2_range<R: Rng>(rng: &mut R, bounds: &Bounds) -> Vec2 {
     // Generate a random Vec2 in the the min/max range of Bounds
     vec2(
         rng.random_range(bounds.x_min..bounds.x_max),
@@ -330,24 +947,76 @@ pub fn rvec2_range(rng: &mut ThreadRng, bounds: &Bounds) -> Vec2 {
 }
 
 // Random generation
-pub fn random_creature(rng: &mut ThreadRng, bounds: &Bounds) -> Creature {
+fn sample_range<R: Rng>(rng: &mut R, (lo, hi): (f32, f32)) -> f32 {
+    // Uniform sample from [lo, hi); an empty range is just that value
+    if lo < hi {
+        rng.random_range(lo..hi)
+    } else {
+        lo
+    }
+}
+
+pub fn random_creature<R: Rng>(rng: &mut R, bounds: &Bounds, traits: &TraitRanges) -> Creature {
     // TODO: different color, max age, size, shape for each species
     let _colors = [WHITE, BLUE, BROWN, GOLD, RED];
     let position = rvec2_range(rng, bounds);
-    let hunger = rng.random_range(10.0..100.0);
-    Creature {
-        // TODO: include some "size" param
-        position,
-        velocity: vec2(0., 0.),
-        facing: 0.,
-        // strength: 1.,
-        dexterity: 1.,
-        hunger,
-        hunger_rate: rng.random_range(1e-4..1e-3),
-        hunger_threshold: rng.random_range(0.25 * hunger..0.75 * hunger),
+    let hunger = sample_range(rng, traits.hunger);
+    let genome = Genome {
+        dexterity: sample_range(rng, traits.dexterity),
+        hunger_rate: sample_range(rng, traits.hunger_rate),
+        hunger_threshold: hunger * sample_range(rng, traits.hunger_threshold),
         color: _colors[rng.random_range(0.._colors.len())],
-        movement_target: None,
-    }
+        mate_preference: sample_range(rng, traits.mate_preference),
+        diet: sample_range(rng, traits.diet),
+        strength: sample_range(rng, traits.strength),
+        boldness: sample_range(rng, traits.boldness),
+        max_age: sample_range(rng, traits.max_age),
+        adult_size: sample_range(rng, traits.adult_size),
+        vision_range: sample_range(rng, traits.vision_range),
+        field_of_view: sample_range(rng, traits.field_of_view),
+        smell: sample_range(rng, traits.smell),
+        density: sample_range(rng, traits.density),
+        controller: ControllerKind::HandCoded,
+        brain: Brain::default(),
+    };
+    // The founding population starts out as adults of mixed ages
+    let mut creature = Creature::new(position, genome, hunger);
+    creature.size = genome.adult_size;
+    creature.age = rng.random_range(0.0..0.5 * genome.max_age);
+    creature.hp = creature.max_hp();
+    creature
+}
+
+pub fn random_world<R: Rng>(rng: &mut R, config: &Config) -> World {
+    // Build a world_width x world_height world with randomly placed food
+    // sources and creatures, as many of each as the config's population
+    let params = config.params;
+    let population = config.population;
+    let bounds = Bounds {
+        x_min: 0.,
+        x_max: params.world_width,
+        y_min: 0.,
+        y_max: params.world_height,
+    };
+
+    let plant_sources: Vec<PlantSource> = (0..population.plants)
+        .map(|_| PlantSource::new_rand(rng, &bounds))
+        .collect();
+    let meat_sources: Vec<MeatSource> = (0..population.meat)
+        .map(|_| MeatSource::new_rand(rng, &bounds))
+        .collect();
+    let creatures: Vec<Creature> = (0..population.creatures)
+        .map(|_| {
+            let mut creature = random_creature(rng, &bounds, &config.traits);
+            if population.neural_fraction > 0. && rng.random::<f32>() < population.neural_fraction {
+                creature.genome.controller = ControllerKind::Neural;
+                creature.genome.brain = Brain::new_rand(rng);
+            }
+            creature
+        })
+        .collect();
+
+    World::new(creatures, plant_sources, meat_sources, params, bounds)
 }
 
 // Game state updates
@@ -356,36 +1025,184 @@ fn update_hunger(creature: &mut Creature, world: &World) {
     // Testing different scaling factors so that the creatures don't spend all
     // their time looking for food
     creature.hunger -=
-        (0.01 + 0.25 * creature.hunger_rate * creature.square_speed()) * world.params.timestep;
+        // Strength and senses are expensive to maintain even at rest
+        (0.01 * creature.genome.strength
+            + world.params.vision_cost * creature.visual_field() / 1e4
+            + world.params.smell_cost * creature.genome.smell
+            // Staying up takes effort for creatures that would sink
+            + world.params.hover_cost
+                * world.params.net_gravity(creature.genome.density).max(0.)
+                * creature.body_mass()
+            + 0.25 * creature.genome.hunger_rate * creature.square_speed())
+            * world.params.timestep;
     creature.hunger = creature.hunger.clamp(0., 100.);
 }
 
-fn find_food(creature: &mut Creature, world: &World) {
-    // Move towards closest food source if hungry
-    // TODO: after adding carnivore/herbivore/omnivore split, need to find
-    // nearest food based on type and also update hunting instinct vs scavenging
-    // instinct
-    let mut nearest_food: Option<(usize, &PlantSource)> = None;
-    let mut distance = f32::MAX;
-    for (id, food) in &world.plant_sources {
-        let food_dist = creature.distance_to_food(&food);
-        if food_dist < distance {
-            distance = food_dist;
-            nearest_food = Some((*id, &food));
+fn update_health<R: Rng>(
+    rng: &mut R,
+    creature: &mut Creature,
+    world: &World,
+) -> Option<DeathCause> {
+    // Age the creature, heal it when fed and check whether it dies this step
+    let params = &world.params;
+    creature.age += params.timestep;
+    if !creature.is_hungry() {
+        creature.hp = (creature.hp + params.hp_regen * params.timestep).min(creature.max_hp());
+    }
+    if creature.hunger <= 0. {
+        return Some(DeathCause::Starvation);
+    }
+    // Senescence: the chance of dying rises linearly past max_age
+    let overage = (creature.age - creature.genome.max_age) / creature.genome.max_age;
+    if overage > 0. && rng.random::<f32>() < params.senescence_rate * overage * params.timestep {
+        return Some(DeathCause::OldAge);
+    }
+    None
+}
+
+fn find_mate(creature: &mut Creature, id: usize, world: &World) {
+    // Target the nearest creature in range that is ready to reproduce and
+    // compatible both ways, unless we are already heading to one
+    if let Some(Target::Mate(mate_id)) = creature.movement_target
+        && world.creatures.contains_key(&mate_id)
+    {
+        return;
+    }
+    let params = &world.params;
+    let nearest_mate = world.nearest_visible(
+        creature,
+        EntityKind::Creature,
+        params.mate_search_radius,
+        |other_id| {
+            let other = &world.creatures[&other_id];
+            other_id != id
+                && other.ready_to_reproduce(params)
+                && creature.genome.accepts(&other.genome)
+                && other.genome.accepts(&creature.genome)
+        },
+    );
+
+    if let Some((mate_id, _)) = nearest_mate {
+        creature.movement_target = Some(Target::Mate(mate_id));
+    }
+}
+
+fn reproduce_asexual<R: Rng>(
+    rng: &mut R,
+    creature: &mut Creature,
+    world: &mut World,
+) -> Option<Creature> {
+    // A ready creature that has not found a mate in time pays
+    // reproduction_cost hunger for an offspring with a mutated copy of its
+    // genome placed close by
+    let params = world.params;
+    if creature.mate_search_time < params.mate_search_time {
+        return None;
+    }
+    creature.hunger -= params.reproduction_cost;
+    creature.reproduction_cooldown = params.reproduction_cooldown;
+    creature.mate_search_time = 0.;
+    if matches!(creature.movement_target, Some(Target::Mate(_))) {
+        creature.movement_target = None;
+    }
+    let offset = vec2(rng.random_range(-5.0..5.0), rng.random_range(-5.0..5.0));
+    let mut position = creature.position + offset;
+    clamp_to_world_bounds(&mut position, &world.bounds, &params);
+    let genome = creature.genome.mutate(rng, &params);
+    world.stats.asexual_births += 1;
+    Some(Creature::new(position, genome, params.reproduction_cost))
+}
+
+fn find_threat(creature: &Creature, id: usize, world: &World) -> Option<Vec2> {
+    // Look for creatures that could hunt this one within its threat radius,
+    // which shrinks with boldness, as far as it can see them; returns the
+    // direction to run in, with closer predators pushing harder
+    let radius = (world.params.threat_radius * (1. - creature.genome.boldness))
+        .min(creature.genome.vision_range);
+    let mut away = Vec2::ZERO;
+    for other_id in world.entities_within(creature.position, radius, EntityKind::Creature) {
+        let other = &world.creatures[&other_id];
+        if other_id == id
+            || !other.can_hunt(creature, &world.params)
+            || !creature.sees(other.position, world)
+        {
+            continue;
+        }
+        let from_other = world.displacement(other.position, creature.position);
+        let distance = from_other.length();
+        if distance < radius {
+            away += from_other.normalize_or_zero() * (1. - distance / radius);
+        }
+    }
+    if away == Vec2::ZERO { None } else { Some(away) }
+}
+
+fn find_food(creature: &mut Creature, id: usize, world: &World) {
+    // Move towards the best food source if hungry: distance is weighted by how
+    // well the creature digests each kind, so herbivores ignore meat,
+    // carnivores ignore plants and omnivores take whatever is effectively
+    // closest
+    // Hunting counts as meat too, but prey fights back so it is weighted by
+    // hunt_cost compared to scavenging. Only what the creature sees counts
+    let mut best_food: Option<Target> = None;
+    let mut cost = f32::MAX;
+    let plant_efficiency = creature.plant_efficiency();
+    if plant_efficiency > 0.
+        && let Some((food_id, distance)) =
+            world.nearest_visible(creature, EntityKind::Plant, f32::MAX, |_| true)
+    {
+        cost = distance / plant_efficiency;
+        best_food = Some(Target::Food(food_id));
+    }
+    let meat_efficiency = creature.meat_efficiency();
+    if meat_efficiency > 0. {
+        if let Some((meat_id, distance)) =
+            world.nearest_visible(creature, EntityKind::Meat, f32::MAX, |_| true)
+            && distance / meat_efficiency < cost
+        {
+            cost = distance / meat_efficiency;
+            best_food = Some(Target::Meat(meat_id));
         }
+        let prey = world.nearest_visible(creature, EntityKind::Creature, f32::MAX, |prey_id| {
+            prey_id != id && creature.can_hunt(&world.creatures[&prey_id], &world.params)
+        });
+        if let Some((prey_id, distance)) = prey
+            && world.params.hunt_cost * distance / meat_efficiency < cost
+        {
+            best_food = Some(Target::Creature(prey_id));
+        }
+    }
+
+    if best_food.is_some() {
+        creature.movement_target = best_food;
     }
+}
+
+// How far up the scent gradient a creature heads before smelling again
+const SCENT_STEP: f32 = 40.;
 
-    if let Some((food_id, _)) = nearest_food {
-        creature.movement_target = Some(Target::Food(food_id));
+fn follow_scent(creature: &mut Creature, world: &World) {
+    // With no food in sight, head a little way towards where it smells
+    // strongest
+    if let Some(uphill) = creature.smell_food(world) {
+        let mut target_pos = creature.position + uphill * SCENT_STEP;
+        clamp_to_world_bounds(&mut target_pos, &world.bounds, &world.params);
+        creature.movement_target = Some(Target::Position(target_pos));
     }
 }
 
-fn clamp_to_world_bounds(v: &mut Vec2, bounds: &Bounds, padding: f32) {
-    v.x = clamp(v.x, bounds.x_min + padding, bounds.x_max - padding);
-    v.y = clamp(v.y, bounds.y_min + padding, bounds.y_max - padding);
+fn clamp_to_world_bounds(v: &mut Vec2, bounds: &Bounds, params: &Params) {
+    // Keep a point 'padding' away from the edges; periodic x wraps instead
+    let padding = params.padding;
+    if params.boundary == BoundaryMode::PeriodicX {
+        *v = bounds.wrap(*v, params.boundary);
+    } else {
+        v.x = v.x.clamp(bounds.x_min + padding, bounds.x_max - padding);
+    }
+    v.y = v.y.clamp(bounds.y_min + padding, bounds.y_max - padding);
 }
 
-fn find_random_walk_target(rng: &mut ThreadRng, creature: &mut Creature, world: &World) {
+fn find_random_walk_target<R: Rng>(rng: &mut R, creature: &mut Creature, world: &World) {
     // Set a target in a cone somewhere in front of the creature if we don't
     // have a target already
     if creature.movement_target.is_some() {
@@ -400,22 +1217,41 @@ fn find_random_walk_target(rng: &mut ThreadRng, creature: &mut Creature, world:
     let dy = distance * angle.sin();
     let mut target_pos = creature.position + Vec2::new(dx, dy);
     // Check if in-bounds
-    clamp_to_world_bounds(&mut target_pos, &world.bounds, world.params.padding);
+    clamp_to_world_bounds(&mut target_pos, &world.bounds, &world.params);
 
     creature.movement_target = Some(Target::Position(target_pos));
     // println!("New movemment target is {:?}", creature.movement_target);
 }
 
 fn apply_bc(creature: &mut Creature, world: &World) {
-    // For now, just repel creatures from the border
-    let mut force = Vec2::ZERO;
     let params = world.params;
     let bounds = world.bounds;
-    // Force strength is just the distance to the edge
-    if creature.position.x < bounds.x_min + params.padding {
-        force.x += params.padding - (creature.position.x - bounds.x_min).max(1.0);
-    } else if creature.position.x > bounds.x_max - params.padding {
-        force.x -= params.padding - (bounds.x_max - creature.position.x).max(1.0);
+    if params.boundary == BoundaryMode::Reflective {
+        creature.position += creature.velocity * params.timestep;
+        reflect(
+            &mut creature.position.x,
+            &mut creature.velocity.x,
+            bounds.x_min,
+            bounds.x_max,
+        );
+        reflect(
+            &mut creature.position.y,
+            &mut creature.velocity.y,
+            bounds.y_min,
+            bounds.y_max,
+        );
+        return;
+    }
+
+    // Repel creatures from the border; force strength is just the distance to
+    // the edge
+    let mut force = Vec2::ZERO;
+    if params.boundary == BoundaryMode::Repulsive {
+        if creature.position.x < bounds.x_min + params.padding {
+            force.x += params.padding - (creature.position.x - bounds.x_min).max(1.0);
+        } else if creature.position.x > bounds.x_max - params.padding {
+            force.x -= params.padding - (bounds.x_max - creature.position.x).max(1.0);
+        }
     }
 
     if creature.position.y < bounds.y_min + params.padding {
@@ -427,10 +1263,25 @@ fn apply_bc(creature: &mut Creature, world: &World) {
     // creature.velocity += force * params.timestep * params.damping;
     creature.velocity += force * params.timestep;
     creature.position += creature.velocity * params.timestep;
+    creature.position = world.wrap_position(creature.position);
 }
 
-pub fn update_world(rng: &mut ThreadRng, world: &mut World) {
-    let new_food_sources = update_food_sources(rng, world);
+fn reflect(x: &mut f32, v: &mut f32, lo: f32, hi: f32) {
+    // Mirror a coordinate that left [lo, hi] back inside and turn it around
+    if *x < lo {
+        *x = (2. * lo - *x).min(hi);
+        *v = v.abs();
+    } else if *x > hi {
+        *x = (2. * hi - *x).max(lo);
+        *v = -v.abs();
+    }
+}
+
+pub fn update_world<R: Rng>(rng: &mut R, world: &mut World) {
+    world.rebuild_grid();
+    let new_food_sources = update_plant_sources(rng, world);
+    update_meat_sources(rng, world);
+    update_scent(world);
     update_creatures(rng, world);
 
     // Add the new plant sources (borrow checker!)
@@ -439,64 +1290,64 @@ pub fn update_world(rng: &mut ThreadRng, world: &mut World) {
             world.add_plant_source(plant_source);
         }
     }
+
+    world.params.plant_regrow_timer += world.params.timestep;
+    world.params.time += world.params.timestep;
+    world.steps += 1;
+
+    let interval = world.params.phylogeny_prune_interval;
+    if interval > 0. && world.params.time - world.phylogeny.last_prune >= interval {
+        world.phylogeny.prune_extinct(world.params.time);
+    }
 }
 
-// TODO: Create 2 functions to update plant and meat sources separately (and turning dead creatures
-// into meat sources will need to be handled)
-fn update_food_sources(rng: &mut ThreadRng, world: &mut World) -> Vec<PlantSource> {
-    // Plant sources
+fn update_plant_sources<R: Rng>(rng: &mut R, world: &mut World) -> Vec<PlantSource> {
     let mut new_plants: Vec<PlantSource> = Vec::new();
-    for (_id, plant) in world.plant_sources.iter_mut() {
-        // Regrow plant
-        if plant.amount < plant.max_amount {
-            plant.amount = clamp(
-                plant.amount + plant.regrow_amount * world.params.timestep,
-                0.0,
-                plant.max_amount,
-            );
-        }
+    let bounds = world.bounds;
+    let params = world.params;
+    let current = &world.current;
+    for plant in world.plant_sources.values_mut() {
+        let flow = current.velocity(&params, &bounds, plant.position);
 
-        // Check for plant reproduction
-        if rng.random::<f32>() <= plant.regrow_freq {
-            // Pick a position somewhat nearby, create a new plant resource
-            // with an amount of '1' and random attributes
+        // Photosynthesis: regrow in the light, shrink in the dark
+        let light = params.light(plant.position.y - bounds.y_min);
+        plant.amount = (plant.amount
+            + plant.regrow_amount * (light - params.light_compensation) * params.timestep)
+            .min(plant.max_amount);
 
+        // Check for plant reproduction, which also needs light
+        if rng.random::<f32>() <= plant.regrow_freq * light {
+            // Pick a position somewhat nearby and downstream, create a new
+            // plant resource with an amount of '1' and random attributes
+            let offset = vec2(rng.random_range(-5.0..5.0), rng.random_range(-5.0..5.0))
+                + flow * params.plant_dispersal_time;
             new_plants.push(PlantSource {
-                position: Vec2 {
-                    x: plant.position.x + rng.random_range(-5.0..5.0),
-                    y: plant.position.y + rng.random_range(-5.0..5.0),
-                },
+                position: keep_in_water(plant.position + offset, &bounds, &params),
                 velocity: Vec2::ZERO,
                 max_amount: rng.random_range(50.0..100.0),
                 amount: 1.,
                 regrow_freq: plant.regrow_freq,
                 regrow_amount: plant.regrow_amount,
+                density: plant.density,
             });
         }
 
-        // Let them drift (y clamped for ground)
-        plant.velocity.x +=
-            rng.random_range(-0.5..0.5) * world.params.timestep * world.params.damping
-                / plant.amount;
-        plant.position.x += plant.velocity.x * world.params.timestep;
-        // Positive y is down (game dev / 10)
-        // TODO: figure out a more natural behaviour, lighter stuff is more
-        // likely to move but currently things drift down too fast, might
-        // need to weight the distribution so it's not just uniform?
-        plant.velocity.y +=
-            rng.random_range(-1.0..2.0) * world.params.timestep * world.params.damping
-                / plant.amount;
-        // Clamp to some terminal velocity
-        plant.velocity.y = clamp(
-            plant.velocity.y,
-            -world.params.food_terminal_velocity,
-            world.params.food_terminal_velocity,
+        // Let them drift, lighter (smaller) plants are jostled more (up to a
+        // point, plants starving in the dark shrink towards nothing), then
+        // sink or float (positive y is down)
+        let jostle = vec2(rng.random_range(-0.5..0.5), rng.random_range(-0.5..0.5));
+        plant.velocity += jostle * params.timestep * params.damping / plant.amount.max(1.);
+        move_food(
+            &mut plant.position,
+            &mut plant.velocity,
+            plant.density,
+            flow,
+            &bounds,
+            &params,
         );
-        plant.position += plant.velocity * world.params.timestep;
-        if plant.position.y > world.bounds.y_max + world.params.padding {
-            plant.position.y = world.bounds.y_max + world.params.padding;
-        }
     }
+    // Plants that starved in the dark (or were grazed to nothing) are gone
+    world.plant_sources.retain(|_id, plant| plant.amount > 0.);
 
     // Regrow a random amount of plant (deprecated once plants can spread)
     // if world.params.plant_regrow_timer >= world.params.plant_regrow_freq {
@@ -507,58 +1358,164 @@ fn update_food_sources(rng: &mut ThreadRng, world: &mut World) -> Vec<PlantSourc
     //     }
     // }
 
-    // Meat sources
+    new_plants
+}
+
+fn move_food(
+    position: &mut Vec2,
+    velocity: &mut Vec2,
+    density: f32,
+    flow: Vec2,
+    bounds: &Bounds,
+    params: &Params,
+) {
+    // One physics step for a plant or meat: drag and net gravity act on its
+    // velocity through the water, which carries it along at 'flow'. Food
+    // that reaches the floor (or the surface) comes to rest there
+    *velocity *= params.damping.powf(params.timestep);
+    velocity.y += params.net_gravity(density) * params.timestep;
+    let moved = *position + (*velocity + flow) * params.timestep;
+    *position = keep_in_water(moved, bounds, params);
+    if position.y != moved.y {
+        velocity.y = 0.;
+    }
+}
+
+fn keep_in_water(v: Vec2, bounds: &Bounds, params: &Params) -> Vec2 {
+    // Food stays between the surface and the floor ('padding' below the
+    // bottom edge) and can't be carried out through the side walls
+    let mut v = bounds.wrap(v, params.boundary);
+    if params.boundary != BoundaryMode::PeriodicX {
+        v.x = v.x.clamp(bounds.x_min, bounds.x_max);
+    }
+    v.y = v.y.clamp(bounds.y_min, bounds.y_max + params.padding);
+    v
+}
+
+fn update_meat_sources<R: Rng>(rng: &mut R, world: &mut World) {
+    // Dead creatures are turned into meat sources by World::kill_creature;
+    // here meat drifts, sinks and decays
+    let params = world.params;
+    let decay_factor = 0.5_f32.powf(params.timestep / params.meat_half_life);
+    let mut decayed: Vec<(Vec2, f32)> = Vec::new();
     for (_id, meat) in world.meat_sources.iter_mut() {
-        // Let them drift down and around as long as they are not on the ground
-        // Positive y is down (game dev / 10)
-        if meat.position.y <= world.bounds.y_max - world.params.padding {
-            // Some random drift and gravity for y
-            meat.velocity.y +=
-                rng.random_range(-1.0..5.0) * world.params.timestep * world.params.damping
-                    / meat.amount;
-            // meat.velocity.y += world.params.timestep * world.params.damping * meat.amount;
-            // Clamp to some terminal velocity
-            meat.velocity.y = clamp(
-                meat.velocity.y,
-                -world.params.food_terminal_velocity,
-                world.params.food_terminal_velocity,
-            );
-            meat.position += meat.velocity * world.params.timestep / meat.amount;
-        } else {
-            meat.velocity.y = 0.
-        }
+        // Exponential decay with the configured half-life
+        let lost = meat.amount * (1. - decay_factor);
+        meat.amount -= lost;
+        decayed.push((meat.position, lost));
 
-        meat.velocity.x +=
-            rng.random_range(-0.1..0.1) * world.params.timestep * world.params.damping;
-        meat.position.x += meat.velocity.x * world.params.timestep;
+        // Let them drift around, then sink (or float) like plants do
+        meat.velocity.x += rng.random_range(-0.1..0.1) * params.timestep * params.damping;
+        let flow = world
+            .current
+            .velocity(&params, &world.bounds, meat.position);
+        move_food(
+            &mut meat.position,
+            &mut meat.velocity,
+            meat.density,
+            flow,
+            &world.bounds,
+            &params,
+        );
     }
+    world
+        .meat_sources
+        .retain(|_id, meat| meat.amount >= params.meat_min_amount);
 
-    new_plants
+    // Part of the decayed mass fertilises the nearest plant in range
+    if params.meat_decay_to_plants > 0. {
+        for (position, lost) in decayed {
+            let nearest_plant = world
+                .nearest(
+                    position,
+                    EntityKind::Plant,
+                    params.meat_fertilise_radius,
+                    |_| true,
+                )
+                .and_then(|(plant_id, _)| world.plant_sources.get_mut(&plant_id));
+            if let Some(plant) = nearest_plant {
+                plant.amount =
+                    (plant.amount + params.meat_decay_to_plants * lost).min(plant.max_amount);
+            }
+        }
+    }
+}
+
+fn update_scent(world: &mut World) {
+    // Food emits scent in proportion to how much of it is left, then the
+    // field spreads out and fades
+    let params = world.params;
+    let emitted = params.scent_emission * params.timestep;
+    for plant in world.plant_sources.values() {
+        world
+            .scent
+            .emit(EntityKind::Plant, plant.position, emitted * plant.amount);
+    }
+    for meat in world.meat_sources.values() {
+        world
+            .scent
+            .emit(EntityKind::Meat, meat.position, emitted * meat.amount);
+    }
+    world
+        .scent
+        .step(params.timestep, params.scent_diffusion, params.scent_decay);
 }
 
-fn update_creatures(rng: &mut ThreadRng, world: &mut World) {
+fn update_creatures<R: Rng>(rng: &mut R, world: &mut World) {
     // Collect all creature IDs, then create new creatures (re-inserting into
     // the hashmap); only works because structs are simple
     let creature_ids: Vec<usize> = world.creatures.keys().cloned().collect();
+    let mut offspring: Vec<(Creature, [Option<usize>; 2])> = Vec::new();
 
     for id in creature_ids {
-        let mut creature = world.creatures[&id];
+        // Creatures killed earlier in this step are skipped
+        let Some(&creature) = world.creatures.get(&id) else {
+            continue;
+        };
+        let mut creature = creature;
         update_hunger(&mut creature, world);
-        if creature.is_hungry() && creature.movement_target.is_none() {
-            find_food(&mut creature, world);
-        } else {
-            find_random_walk_target(rng, &mut creature, world);
+        if let Some(cause) = update_health(rng, &mut creature, world) {
+            world.creatures.insert(id, creature);
+            world.kill_creature(id, cause);
+            continue;
         }
-        let reached = creature.move_to_target(world);
-        if reached {
-            creature.handle_reached_target(world);
+        creature.reproduction_cooldown -= world.params.timestep;
+        if creature.ready_to_reproduce(&world.params) {
+            find_mate(&mut creature, id, world);
+            creature.mate_search_time += world.params.timestep;
+            offspring.extend(
+                reproduce_asexual(rng, &mut creature, world).map(|c| (c, [Some(id), None])),
+            );
+        } else {
+            // Not ready (any more): the next search starts from scratch and
+            // we stop chasing the mate
+            creature.mate_search_time = 0.;
+            if matches!(creature.movement_target, Some(Target::Mate(_))) {
+                creature.movement_target = None;
+            }
         }
+        let controller = creature.genome.controller;
+        offspring.extend(
+            controller
+                .act(rng, &mut creature, id, world)
+                .map(|(child, mate)| (child, [Some(id), Some(mate)])),
+        );
 
+        // The current carries the creature along on top of its own swimming,
+        // and creatures lighter than water float up unless they swim against it
+        creature.position += world.current_at(creature.position) * world.params.timestep;
+        creature.velocity.y +=
+            world.params.net_gravity(creature.genome.density).min(0.) * world.params.timestep;
         apply_bc(&mut creature, world);
         creature.update_facing();
         world.creatures.insert(id, creature); // Replace the old creature
     }
 
+    // Offspring are only added once everyone has moved (borrow checker!)
+    for (child, parents) in offspring {
+        world.add_offspring(child, parents);
+    }
+
     // The mutable version the borrow checker hates:
     // for (_id, creature) in world.creatures.iter_mut() {
     //     update_hunger(creature, world);
@@ -582,9 +1539,35 @@ fn update_creatures(rng: &mut ThreadRng, world: &mut World) {
 * - Add species that have different attributes (currently all creatures are
 * identical); each species can be its own hashmap, or we can have a hashmap of
 * hashmaps
-* - Add reproduction to pass on traits
-* - Add hunting & predators
 * - Fix the food-chasing behaviour so that creatures don't chase food around
 * without eating (something to do with acceleration?); generally, give them
 * better movement
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64, steps: usize) -> String {
+        // A world stepped from 'seed', serialized together with its RNG
+        let mut config = Config::default();
+        config.params.seed = seed;
+        config.population.neural_fraction = 0.5; // exercise both controllers
+        let mut rng = seeded_rng(seed);
+        let mut world = random_world(&mut rng, &config);
+        for _ in 0..steps {
+            update_world(&mut rng, &mut world);
+        }
+        serde_json::to_string(&(&world, &rng)).unwrap()
+    }
+
+    #[test]
+    fn same_seed_gives_identical_worlds() {
+        assert_eq!(run(7, 500), run(7, 500));
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        assert_ne!(run(7, 500), run(8, 500));
+    }
+}
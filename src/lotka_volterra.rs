@@ -0,0 +1,412 @@
+use std::f64::consts::PI;
+
+// Reference predator-prey models to compare simulation runs against. Prey x
+// and predators y follow
+//   dx/dt = alpha x (1 - x / capacity) - beta x y
+//   dy/dt = delta x y - gamma y
+// where the classic model has an infinite capacity. Everything here works on
+// plain time series, e.g. the prey and predators columns of a Recorder CSV.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LvModel {
+    Classic,      // neutral cycles whose amplitude depends on the start
+    LogisticPrey, // prey limited by a carrying capacity, cycles damp out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LvParams {
+    pub alpha: f64,    // prey growth rate
+    pub beta: f64,     // prey eaten per predator per prey per unit time
+    pub gamma: f64,    // predator death rate
+    pub delta: f64,    // predator growth per prey eaten
+    pub capacity: f64, // prey carrying capacity, f64::INFINITY for Classic
+}
+
+impl LvParams {
+    pub fn derivative(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.alpha * x * (1. - x / self.capacity) - self.beta * x * y,
+            self.delta * x * y - self.gamma * y,
+        )
+    }
+
+    pub fn equilibrium(&self) -> (f64, f64) {
+        // Coexistence fixed point (negative predators mean it doesn't exist
+        // because the capacity can't feed any)
+        let x = self.gamma / self.delta;
+        (x, self.alpha / self.beta * (1. - x / self.capacity))
+    }
+
+    pub fn linear_period(&self) -> Option<f64> {
+        // Period of small oscillations around the equilibrium, from the
+        // imaginary part of the Jacobian's eigenvalues there; None if the
+        // equilibrium is not a spiral
+        let (x, y) = self.equilibrium();
+        let trace = -self.alpha * x / self.capacity;
+        let det = self.beta * self.delta * x * y;
+        let disc = det - 0.25 * trace * trace;
+        (y > 0. && disc > 0.).then(|| 2. * PI / disc.sqrt())
+    }
+}
+
+pub fn integrate(params: &LvParams, start: (f64, f64), times: &[f64]) -> Vec<(f64, f64)> {
+    // State at each of 'times' (ascending, the first being the start) with
+    // fixed-step RK4, splitting sample intervals so steps stay small compared
+    // to the model's own time scales. The split is capped so that absurd
+    // parameters (which a fit can try) stay cheap, if inaccurate
+    const MAX_SUBSTEPS: f64 = 100.;
+    let rate = params.alpha.max(params.gamma).max(1e-9);
+    let max_dt = 0.05 / rate;
+    let mut state = start;
+    let mut states = Vec::with_capacity(times.len());
+    for (i, &t) in times.iter().enumerate() {
+        if i > 0 {
+            let span = t - times[i - 1];
+            let n = (span / max_dt).ceil().clamp(1., MAX_SUBSTEPS) as usize;
+            let dt = span / n as f64;
+            for _ in 0..n {
+                state = rk4_step(params, state, dt);
+            }
+        }
+        states.push(state);
+    }
+    states
+}
+
+fn rk4_step(params: &LvParams, (x, y): (f64, f64), dt: f64) -> (f64, f64) {
+    let k1 = params.derivative((x, y));
+    let k2 = params.derivative((x + 0.5 * dt * k1.0, y + 0.5 * dt * k1.1));
+    let k3 = params.derivative((x + 0.5 * dt * k2.0, y + 0.5 * dt * k2.1));
+    let k4 = params.derivative((x + dt * k3.0, y + dt * k3.1));
+    // Populations can't go negative, and a blown-up step shouldn't poison the
+    // rest of the trajectory with NaNs
+    let step = |v: f64, a: f64, b: f64, c: f64, d: f64| {
+        let v = v + dt / 6. * (a + 2. * b + 2. * c + d);
+        if v.is_finite() { v.max(0.) } else { f64::MAX }
+    };
+    (
+        step(x, k1.0, k2.0, k3.0, k4.0),
+        step(y, k1.1, k2.1, k3.1, k4.1),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LvFit {
+    pub model: LvModel,
+    pub params: LvParams,
+    pub start: (f64, f64), // fitted initial prey and predator counts
+    // coefficient of determination of the model trajectory for each series
+    // (1 is a perfect fit, 0 no better than the series mean) and RMSE in
+    // individuals
+    pub prey_r_squared: f64,
+    pub predator_r_squared: f64,
+    pub prey_rmse: f64,
+    pub predator_rmse: f64,
+}
+
+impl LvFit {
+    pub fn r_squared(&self) -> f64 {
+        0.5 * (self.prey_r_squared + self.predator_r_squared)
+    }
+
+    pub fn trajectory(&self, times: &[f64]) -> Vec<(f64, f64)> {
+        integrate(&self.params, self.start, times)
+    }
+}
+
+pub fn fit(model: LvModel, times: &[f64], prey: &[f64], predators: &[f64]) -> Option<LvFit> {
+    // Least-squares fit of a model trajectory to observed counts. Both series
+    // are weighted by their spread so neither dominates. Starts from a
+    // regression on per-capita growth rates and refines with Nelder-Mead over
+    // log parameters (which keeps them positive); the initial counts are
+    // fitted too since the first sample is as noisy as any other. None if
+    // there are too few samples or either population never changes
+    let n = times.len();
+    if n < 4 || prey.len() != n || predators.len() != n {
+        return None;
+    }
+    let (prey_mean, prey_var) = mean_var(prey);
+    let (pred_mean, pred_var) = mean_var(predators);
+    if prey_var <= 0. || pred_var <= 0. {
+        return None;
+    }
+
+    let guess = initial_guess(model, times, prey, predators, prey_mean, pred_mean);
+    let to_params = |v: &[f64]| LvParams {
+        alpha: v[0].exp(),
+        beta: v[1].exp(),
+        gamma: v[2].exp(),
+        delta: v[3].exp(),
+        capacity: match model {
+            LvModel::Classic => f64::INFINITY,
+            LvModel::LogisticPrey => v[6].exp(),
+        },
+    };
+    let cost = |v: &[f64]| {
+        let states = integrate(&to_params(v), (v[4].exp(), v[5].exp()), times);
+        states
+            .iter()
+            .zip(prey.iter().zip(predators))
+            .map(|(&(x, y), (&px, &py))| (x - px).powi(2) / prey_var + (y - py).powi(2) / pred_var)
+            .sum::<f64>()
+    };
+    let mut start = vec![
+        guess.alpha.ln(),
+        guess.beta.ln(),
+        guess.gamma.ln(),
+        guess.delta.ln(),
+        prey[0].max(1.).ln(),
+        predators[0].max(1.).ln(),
+    ];
+    if model == LvModel::LogisticPrey {
+        start.push(guess.capacity.ln());
+    }
+    let best = nelder_mead(&cost, start, 0.5, 4000);
+
+    let params = to_params(&best);
+    let fitted_start = (best[4].exp(), best[5].exp());
+    let states = integrate(&params, fitted_start, times);
+    let sse = |observed: &[f64], pick: fn(&(f64, f64)) -> f64| {
+        states
+            .iter()
+            .zip(observed)
+            .map(|(s, o)| (pick(s) - o).powi(2))
+            .sum::<f64>()
+    };
+    let (prey_sse, pred_sse) = (sse(prey, |s| s.0), sse(predators, |s| s.1));
+    Some(LvFit {
+        model,
+        params,
+        start: fitted_start,
+        prey_r_squared: 1. - prey_sse / (prey_var * n as f64),
+        predator_r_squared: 1. - pred_sse / (pred_var * n as f64),
+        prey_rmse: (prey_sse / n as f64).sqrt(),
+        predator_rmse: (pred_sse / n as f64).sqrt(),
+    })
+}
+
+fn initial_guess(
+    model: LvModel,
+    times: &[f64],
+    prey: &[f64],
+    predators: &[f64],
+    prey_mean: f64,
+    pred_mean: f64,
+) -> LvParams {
+    // Per-capita growth rates between samples are linear in the parameters:
+    //   d ln x/dt = alpha - (alpha / capacity) x - beta y
+    //   d ln y/dt = delta x - gamma y
+    // so ordinary least squares on them gives a rough starting point. Anything
+    // with the wrong sign falls back to a value that at least has the right
+    // scale
+    let mut rows_x = Vec::new();
+    let mut rows_y = Vec::new();
+    for i in 1..times.len() {
+        let dt = times[i] - times[i - 1];
+        let (x0, x1, y0, y1) = (prey[i - 1], prey[i], predators[i - 1], predators[i]);
+        if dt <= 0. || x0 <= 0. || x1 <= 0. || y0 <= 0. || y1 <= 0. {
+            continue;
+        }
+        let (x, y) = (0.5 * (x0 + x1), 0.5 * (y0 + y1));
+        rows_x.push((x, y, (x1 / x0).ln() / dt));
+        rows_y.push((x, (y1 / y0).ln() / dt));
+    }
+    let span = (times[times.len() - 1] - times[0]).max(f64::EPSILON);
+    let rate = 2. * PI / span; // one cycle over the whole run
+
+    let (alpha, beta, capacity) = match model {
+        LvModel::Classic => {
+            let (a, b) = linear_fit(rows_x.iter().map(|&(_, y, r)| (y, r)));
+            (a, -b, f64::INFINITY)
+        }
+        LvModel::LogisticPrey => {
+            let (a, bx, by) = plane_fit(&rows_x);
+            (a, -by, if bx < 0. { -a / bx } else { f64::NAN })
+        }
+    };
+    let (gamma, delta) = {
+        let (a, b) = linear_fit(rows_y.iter().copied());
+        (-a, b)
+    };
+    let valid = |v: f64, fallback: f64| if v.is_finite() && v > 0. { v } else { fallback };
+    LvParams {
+        alpha: valid(alpha, rate),
+        beta: valid(beta, rate / pred_mean.max(1.)),
+        gamma: valid(gamma, rate),
+        delta: valid(delta, rate / prey_mean.max(1.)),
+        capacity: match model {
+            LvModel::Classic => f64::INFINITY,
+            LvModel::LogisticPrey => valid(capacity, 2. * prey.iter().cloned().fold(1., f64::max)),
+        },
+    }
+}
+
+fn linear_fit(points: impl Iterator<Item = (f64, f64)>) -> (f64, f64) {
+    // Intercept and slope of the least-squares line through (x, y) points
+    let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0., 0., 0., 0., 0.);
+    for (x, y) in points {
+        n += 1.;
+        sx += x;
+        sy += y;
+        sxx += x * x;
+        sxy += x * y;
+    }
+    let slope = (n * sxy - sx * sy) / (n * sxx - sx * sx);
+    ((sy - slope * sx) / n, slope)
+}
+
+fn plane_fit(points: &[(f64, f64, f64)]) -> (f64, f64, f64) {
+    // Coefficients (c, a, b) of the least-squares plane z = c + a x + b y,
+    // solving the 3x3 normal equations with Cramer's rule
+    let mut m = [[0.; 3]; 3];
+    let mut v = [0.; 3];
+    for &(x, y, z) in points {
+        let row = [1., x, y];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += row[i] * row[j];
+            }
+            v[i] += row[i] * z;
+        }
+    }
+    let det = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(&m);
+    let solve = |col: usize| {
+        let mut mc = m;
+        for (row, value) in mc.iter_mut().zip(v) {
+            row[col] = value;
+        }
+        det(&mc) / d
+    };
+    (solve(0), solve(1), solve(2))
+}
+
+fn nelder_mead(
+    f: &impl Fn(&[f64]) -> f64,
+    start: Vec<f64>,
+    step: f64,
+    max_iter: usize,
+) -> Vec<f64> {
+    // Derivative-free minimisation with the standard reflection, expansion,
+    // contraction and shrink moves; NaN costs count as infinitely bad
+    let cost = |v: &[f64]| {
+        let c = f(v);
+        if c.is_nan() { f64::INFINITY } else { c }
+    };
+    let dim = start.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=dim)
+        .map(|i| {
+            let mut v = start.clone();
+            if i > 0 {
+                v[i - 1] += step;
+            }
+            let c = cost(&v);
+            (v, c)
+        })
+        .collect();
+    let blend = |a: &[f64], b: &[f64], t: f64| -> Vec<f64> {
+        a.iter().zip(b).map(|(a, b)| a + t * (b - a)).collect()
+    };
+    for _ in 0..max_iter {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, worst) = (simplex[0].1, simplex[dim].1);
+        if (worst - best).abs() <= 1e-10 * (1. + best.abs()) {
+            break;
+        }
+        let mut centroid = vec![0.; dim];
+        for (v, _) in &simplex[..dim] {
+            for (c, x) in centroid.iter_mut().zip(v) {
+                *c += x / dim as f64;
+            }
+        }
+        let reflected = blend(&centroid, &simplex[dim].0, -1.);
+        let reflected_cost = cost(&reflected);
+        if reflected_cost < best {
+            let expanded = blend(&centroid, &simplex[dim].0, -2.);
+            let expanded_cost = cost(&expanded);
+            simplex[dim] = if expanded_cost < reflected_cost {
+                (expanded, expanded_cost)
+            } else {
+                (reflected, reflected_cost)
+            };
+        } else if reflected_cost < simplex[dim - 1].1 {
+            simplex[dim] = (reflected, reflected_cost);
+        } else {
+            let contracted = blend(&centroid, &simplex[dim].0, 0.5);
+            let contracted_cost = cost(&contracted);
+            if contracted_cost < worst {
+                simplex[dim] = (contracted, contracted_cost);
+            } else {
+                let best = simplex[0].0.clone();
+                for (v, c) in simplex.iter_mut().skip(1) {
+                    *v = blend(&best, v, 0.5);
+                    *c = cost(v);
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    simplex.swap_remove(0).0
+}
+
+fn mean_var(values: &[f64]) -> (f64, f64) {
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, var)
+}
+
+pub fn period(times: &[f64], series: &[f64]) -> Option<f64> {
+    // Mean time between upward crossings of the series mean. Crossings need
+    // to go from below mean - h to above mean + h, with h a tenth of the
+    // standard deviation, so noise around the mean isn't counted as cycles
+    let (mean, var) = mean_var(series);
+    let h = 0.1 * var.sqrt();
+    let mut below = false;
+    let mut crossings = Vec::new();
+    for (&t, &v) in times.iter().zip(series) {
+        if v < mean - h {
+            below = true;
+        } else if below && v > mean + h {
+            below = false;
+            crossings.push(t);
+        }
+    }
+    (crossings.len() >= 2)
+        .then(|| (crossings[crossings.len() - 1] - crossings[0]) / (crossings.len() - 1) as f64)
+}
+
+pub fn phase_lag(times: &[f64], prey: &[f64], predators: &[f64]) -> Option<f64> {
+    // How long predator numbers trail prey numbers: the shift (up to one prey
+    // period, or half the run without one) that best correlates the two
+    // series. Assumes evenly spaced samples, as a Recorder writes them
+    let n = times.len();
+    if n < 4 || prey.len() != n || predators.len() != n {
+        return None;
+    }
+    let dt = (times[n - 1] - times[0]) / (n - 1) as f64;
+    if dt <= 0. {
+        return None;
+    }
+    let (prey_mean, _) = mean_var(prey);
+    let (pred_mean, _) = mean_var(predators);
+    let max_lag = match period(times, prey) {
+        Some(p) => ((p / dt).round() as usize).min(n - 2),
+        None => n / 2,
+    };
+    let correlation = |lag: usize| {
+        let pairs = prey[..n - lag].iter().zip(&predators[lag..]);
+        pairs
+            .map(|(x, y)| (x - prey_mean) * (y - pred_mean))
+            .sum::<f64>()
+            / (n - lag) as f64
+    };
+    (0..=max_lag)
+        .map(|lag| (lag, correlation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(lag, _)| lag as f64 * dt)
+}
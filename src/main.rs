@@ -1,39 +1,58 @@
 use std::f32::consts::PI;
 
-use ::rand::rngs::ThreadRng;
-use evosim::*;
+use ::rand::Rng;
+use evosim::{
+    cli::arg_value, config::Config, controller::ControllerKind, current::CurrentKind,
+    recorder::Recorder, spatial::EntityKind, *,
+};
 use macroquad::{
     miniquad::window::{set_window_position, set_window_size},
     prelude::*,
 };
 
-fn reset(rng: &mut ThreadRng) -> World {
-    // Initialise parameters
-    let params = Params::default();
-    let bounds = Bounds {
-        x_min: 0.,
-        x_max: params.window_width,
-        y_min: 0.,
-        y_max: params.window_height,
-    };
+fn parse_seed(args: &[String], config: Option<&Config>) -> u64 {
+    // Use the seed from '--seed <u64>' if given, then the one set in the
+    // config file, otherwise draw a fresh one so that every run is still
+    // reproducible from the seed shown in the UI
+    arg_value(args, "--seed")
+        .or_else(|| config.and_then(|config| config.seed))
+        .unwrap_or_else(|| ::rand::rng().random())
+}
 
-    // Spawn in food sources
-    let num_plant: usize = 10;
-    let num_meat: usize = 5;
-    let plant_sources: Vec<PlantSource> = (0..num_plant)
-        .map(|_| PlantSource::new_rand(rng, &bounds))
-        .collect::<Vec<PlantSource>>();
-    let meat_sources: Vec<MeatSource> = (0..num_meat)
-        .map(|_| MeatSource::new_rand(rng, &bounds))
-        .collect::<Vec<MeatSource>>();
-
-    // Spawn in creatures
-    let num_creatures: usize = 20;
-    let creatures: Vec<Creature> = (0..num_creatures)
-        .map(|_| random_creature(rng, &bounds))
-        .collect::<Vec<Creature>>();
-
-    World::new(creatures, plant_sources, meat_sources, params, bounds)
+fn parse_config(args: &[String]) -> Option<Config> {
+    // Config file given with '--config <path>'
+    let path: String = arg_value(args, "--config")?;
+    match Config::load(&path) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("invalid config {}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+// Where F5 saves and F9 loads snapshots; '.json' so it can be inspected
+const SNAPSHOT_PATH: &str = "evosim.snapshot.json";
+// Where F6 writes the phylogeny as a Newick tree
+const NEWICK_PATH: &str = "evosim.tree.nwk";
+
+fn open_recorder(config: &Config) -> Option<Recorder> {
+    // CSV recorder for the run if the config names an output file
+    let path = config.recorder.path.as_ref()?;
+    match Recorder::create(path, config.recorder.every) {
+        Ok(recorder) => Some(recorder),
+        Err(e) => {
+            eprintln!("could not create recording '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn reset(rng: &mut SimRng, config: &Config, seed: u64) -> World {
+    // Spawn in food sources and creatures as set up in the config
+    let mut config = config.clone();
+    config.params.seed = seed;
+    random_world(rng, &config)
 }
 
 fn draw_fps(x: f32, y: f32, font_size: f32) {
@@ -48,15 +67,115 @@ fn draw_fps(x: f32, y: f32, font_size: f32) {
 
 fn draw_ui(x: f32, y: f32, font_size: f32, world: &World) {
     let ui_text = format!(
-        "Current time = {:.2}, dt = {:.2e}, food regrow timer = {:.2}, regrow frequency = {:.2}",
+        "Seed = {}, current time = {:.2}, dt = {:.2e}, food regrow timer = {:.2}, regrow frequency = {:.2}, births (sexual/asexual) = {}/{}, starved/eaten/old age = {}/{}/{}",
+        world.params.seed,
         world.params.time,
         world.params.timestep,
         world.params.plant_regrow_timer,
-        world.params.plant_regrow_freq
+        world.params.plant_regrow_freq,
+        world.stats.sexual_births,
+        world.stats.asexual_births,
+        world.stats.starvation_deaths,
+        world.stats.predation_deaths,
+        world.stats.old_age_deaths,
     );
     draw_text(ui_text.as_str(), x, y, font_size, BLACK);
 }
 
+struct View {
+    /*
+     * The part of the world shown on screen: 'target' is the world position at
+     * the centre of the screen and 'scale' the number of screen pixels per
+     * world unit. Scroll to zoom around the cursor, drag to pan, Home resets.
+     */
+    target: Vec2,
+    scale: f32,
+    last_mouse: Vec2,
+}
+
+impl View {
+    fn fit(bounds: &Bounds) -> Self {
+        // Show the whole world
+        let scale = (screen_width() / bounds.width()).min(screen_height() / bounds.height());
+        Self {
+            target: vec2(
+                0.5 * (bounds.x_min + bounds.x_max),
+                0.5 * (bounds.y_min + bounds.y_max),
+            ),
+            scale,
+            last_mouse: mouse_position().into(),
+        }
+    }
+
+    fn camera(&self) -> Camera2D {
+        // Negative y zoom keeps y pointing down like screen coordinates
+        Camera2D {
+            target: self.target,
+            zoom: vec2(
+                2. * self.scale / screen_width(),
+                -2. * self.scale / screen_height(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn handle_input(&mut self, bounds: &Bounds) {
+        let mouse: Vec2 = mouse_position().into();
+        let screen_center = 0.5 * vec2(screen_width(), screen_height());
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0. {
+            // Zoom around the cursor: keep the world point under it fixed
+            let under_mouse = self.camera().screen_to_world(mouse);
+            self.scale = (self.scale * 1.1_f32.powf(wheel.signum())).clamp(0.05, 50.);
+            self.target = under_mouse - (mouse - screen_center) / self.scale;
+        }
+        if is_mouse_button_down(MouseButton::Left) {
+            self.target -= (mouse - self.last_mouse) / self.scale;
+        }
+        if is_key_pressed(KeyCode::Home) {
+            *self = View::fit(bounds);
+        }
+        self.last_mouse = mouse;
+    }
+
+    fn visible(&self, position: Vec2, margin: f32) -> bool {
+        // Whether a point (plus margin) is inside the visible part of the world
+        let half = 0.5 * vec2(screen_width(), screen_height()) / self.scale + Vec2::splat(margin);
+        let d = position - self.target;
+        d.x.abs() <= half.x && d.y.abs() <= half.y
+    }
+}
+
+fn wrapped_xs(x: f32, margin: f32, world: &World) -> Vec<f32> {
+    // With periodic x, things within 'margin' of an edge are also drawn on the
+    // other side so they slide across the seam instead of popping
+    let mut xs = vec![x];
+    if world.params.boundary == BoundaryMode::PeriodicX {
+        let bounds = world.bounds;
+        if x < bounds.x_min + margin {
+            xs.push(x + bounds.width());
+        } else if x > bounds.x_max - margin {
+            xs.push(x - bounds.width());
+        }
+    }
+    xs
+}
+
+fn draw_arrow(from: Vec2, to: Vec2, thickness: f32, color: Color) {
+    // A line with a small triangular head at 'to'
+    let along = to - from;
+    let length = along.length();
+    if length < thickness {
+        return;
+    }
+    let dir = along / length;
+    let head = (0.3 * length).min(8. * thickness);
+    let base = to - dir * head;
+    let side = dir.perp() * 0.5 * head;
+    draw_line(from.x, from.y, base.x, base.y, thickness, color);
+    draw_triangle(to, base + side, base - side, color);
+}
+
 fn lerp_color(c1: Color, c2: Color, s: f32) -> Color {
     // Lerps between two colors; v should be a f32 between 0 and 1 (inclusive)
     // that is the percent between c1 and c2
@@ -69,8 +188,28 @@ fn lerp_color(c1: Color, c2: Color, s: f32) -> Color {
 #[macroquad::main("EvoSim")]
 async fn main() {
     // Initial setup
-    let mut rng: ThreadRng = ::rand::rng();
-    let mut world = reset(&mut rng);
+    let args: Vec<String> = std::env::args().collect();
+    let config_file = parse_config(&args);
+    let config = config_file.clone().unwrap_or_default();
+    // A snapshot given with '--load' that can't be read is reported and the
+    // run starts from the config instead
+    let load: Option<String> = arg_value(&args, "--load");
+    let loaded = load.and_then(|path| match World::load(&path) {
+        Ok(loaded) => Some(loaded),
+        Err(e) => {
+            eprintln!("could not load snapshot '{}': {}", path, e);
+            None
+        }
+    });
+    let (mut world, mut rng) = loaded.unwrap_or_else(|| {
+        let seed = parse_seed(&args, config_file.as_ref());
+        let mut rng = seeded_rng(seed);
+        (reset(&mut rng, &config, seed), rng)
+    });
+    let mut recorder = open_recorder(&config);
+    if let Some(r) = recorder.as_mut() {
+        r.sample(&world).expect("could not write recording");
+    }
     set_window_position(1000, 0);
     set_window_size(
         world.params.window_width as u32,
@@ -81,79 +220,203 @@ async fn main() {
     let dark_blue = Color::new(0.0, 0.2, 0.5, 1.0);
     let plant_color = Color::new(0.3, 0.7, 0.6, 1.0); // sea green
     let meat_color = Color::new(1.0, 0.6, 0.6, 1.0); // salmon
+    let mut view = View::fit(&world.bounds);
+    let mut show_scent = false; // S toggles the scent overlay
+    let mut show_current = false; // C toggles the current arrows
     // let mut is_paused = false;
     // Main render loop
     loop {
-        // Dynamic screen sizing
-        world.params.window_width = screen_width();
-        world.params.window_height = screen_height();
-        let params = world.params;
+        view.handle_input(&world.bounds);
+        // Snapshots: F5 saves the current world, F9 restores the last save,
+        // F6 exports the phylogeny
+        if is_key_pressed(KeyCode::F5) {
+            match world.save(&rng, SNAPSHOT_PATH) {
+                Ok(()) => println!("saved snapshot to {}", SNAPSHOT_PATH),
+                Err(e) => eprintln!("could not save snapshot: {}", e),
+            }
+        }
+        if is_key_pressed(KeyCode::F6) {
+            let tree = world.phylogeny.to_newick(world.params.time);
+            match std::fs::write(NEWICK_PATH, tree + "\n") {
+                Ok(()) => println!("wrote phylogeny to {}", NEWICK_PATH),
+                Err(e) => eprintln!("could not write phylogeny: {}", e),
+            }
+        }
+        if is_key_pressed(KeyCode::S) {
+            show_scent = !show_scent;
+        }
+        if is_key_pressed(KeyCode::C) {
+            show_current = !show_current;
+        }
+        if is_key_pressed(KeyCode::F9) {
+            match World::load(SNAPSHOT_PATH) {
+                Ok((loaded, loaded_rng)) => {
+                    (world, rng) = (loaded, loaded_rng);
+                    // The snapshot's world may have a different size
+                    view = View::fit(&world.bounds);
+                    println!("loaded snapshot from {}", SNAPSHOT_PATH);
+                }
+                Err(e) => eprintln!("could not load snapshot: {}", e),
+            }
+        }
+        let bounds = world.bounds;
+        set_camera(&view.camera());
         // Background (clear then overwrite with ocean)
         clear_background(BLACK);
         // Smaller steps (dividing by bigger number) create finer bars
-        let step = (world.params.window_height / 50.0) as usize;
-        // Draw a rectangle of step-px lines by interpolating lightblue -> darkblue
-        for y in (0..params.window_height as i32).step_by(step) {
-            let color = lerp_color(light_blue, dark_blue, y as f32 / params.window_height);
-            draw_line(
-                0.0,
-                y as f32,
-                params.window_width,
-                y as f32,
-                step as f32,
-                color,
-            );
+        let step = bounds.height() / 50.0;
+        // Draw a rectangle of step-px lines by interpolating lightblue -> darkblue,
+        // following the light plants get if it depends on depth
+        for i in 0..50 {
+            let y = bounds.y_min + i as f32 * step;
+            let darkness = if world.params.light_attenuation > 0. {
+                1. - world.light(y)
+            } else {
+                i as f32 / 50.0
+            };
+            let color = lerp_color(light_blue, dark_blue, darkness);
+            draw_line(bounds.x_min, y, bounds.x_max, y, step, color);
         }
         // Update last line (otherwise will be black)
         draw_line(
-            0.0,
-            params.window_height,
-            params.window_width,
-            params.window_height,
-            step as f32,
+            bounds.x_min,
+            bounds.y_max,
+            bounds.x_max,
+            bounds.y_max,
+            step,
             Color::new(0.95, 0.74, 0.15, 0.8),
         );
         //
         // Update world state
         update_world(&mut rng, &mut world);
+        // The window can be closed at any moment, so flush every frame
+        if let Some(r) = recorder.as_mut()
+            && let Err(e) = r.step(&world).and_then(|_| r.flush())
+        {
+            eprintln!("stopped recording: {}", e);
+            recorder = None;
+        }
+
+        // Scent overlay: each cell tinted by the plant and meat scent in it,
+        // fully opaque-ish well above the smell threshold
+        if show_scent {
+            let scent = &world.scent;
+            let cell = scent.cell();
+            let tint = |c: f32, color: Color| {
+                let alpha = 0.6 * c / (c + 4. * world.params.scent_threshold);
+                Color { a: alpha, ..color }
+            };
+            for row in 0..scent.rows() {
+                for col in 0..scent.cols() {
+                    let corner =
+                        vec2(bounds.x_min, bounds.y_min) + vec2(col as f32, row as f32) * cell;
+                    if !view.visible(corner + 0.5 * cell, cell.max_element()) {
+                        continue;
+                    }
+                    for (kind, color) in [
+                        (EntityKind::Plant, plant_color),
+                        (EntityKind::Meat, meat_color),
+                    ] {
+                        let color = tint(scent.concentration(kind, col, row), color);
+                        if color.a > 0.02 {
+                            draw_rectangle(corner.x, corner.y, cell.x, cell.y, color);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Current overlay: arrows on a regular grid, as long as the spacing
+        // at current_speed
+        if show_current && world.params.current != CurrentKind::None {
+            let spacing = 80.;
+            let arrow_scale = 0.8 * spacing / world.params.current_speed.max(f32::EPSILON);
+            let (cols, rows) = (
+                (bounds.width() / spacing) as usize,
+                (bounds.height() / spacing) as usize,
+            );
+            for row in 0..rows {
+                for col in 0..cols {
+                    let from = vec2(bounds.x_min, bounds.y_min)
+                        + spacing * vec2(col as f32 + 0.5, row as f32 + 0.5);
+                    if !view.visible(from, spacing) {
+                        continue;
+                    }
+                    let flow = world.current_at(from) * arrow_scale;
+                    draw_arrow(
+                        from - 0.5 * flow,
+                        from + 0.5 * flow,
+                        1.5 / view.scale,
+                        WHITE,
+                    );
+                }
+            }
+        }
 
         // Render plant sources
         for plant in world.plant_sources.values() {
-            draw_circle(
-                plant.position.x,
-                plant.position.y,
-                plant.amount / plant.max_amount * 8.,
-                plant_color,
-            )
+            for x in wrapped_xs(plant.position.x, 8., &world) {
+                if !view.visible(vec2(x, plant.position.y), 8.) {
+                    continue;
+                }
+                draw_circle(
+                    x,
+                    plant.position.y,
+                    plant.amount / plant.max_amount * 8.,
+                    plant_color,
+                )
+            }
         }
 
         // Render meat sources
         for meat in world.meat_sources.values() {
-            draw_circle(
-                meat.position.x,
-                meat.position.y,
-                meat.amount / meat.max_amount * 8.,
-                meat_color,
-            )
+            for x in wrapped_xs(meat.position.x, 8., &world) {
+                if !view.visible(vec2(x, meat.position.y), 8.) {
+                    continue;
+                }
+                draw_circle(
+                    x,
+                    meat.position.y,
+                    meat.amount / meat.max_amount * 8.,
+                    meat_color,
+                )
+            }
         }
 
         // Render creatures
         for creature in world.creatures.values() {
-            draw_poly(
-                creature.position.x,
-                creature.position.y,
-                3,
-                6.,
-                creature.facing * 180. / PI,
-                creature.color,
-            );
+            let radius = 6. * creature.size;
+            for x in wrapped_xs(creature.position.x, radius, &world) {
+                if !view.visible(vec2(x, creature.position.y), radius) {
+                    continue;
+                }
+                draw_poly(
+                    x,
+                    creature.position.y,
+                    3,
+                    radius,
+                    creature.facing * 180. / PI,
+                    creature.genome.color.into(),
+                );
+                // Outline the ones steered by a neural network
+                if creature.genome.controller == ControllerKind::Neural {
+                    draw_poly_lines(
+                        x,
+                        creature.position.y,
+                        3,
+                        radius,
+                        creature.facing * 180. / PI,
+                        1. / view.scale,
+                        BLACK,
+                    );
+                }
+            }
         }
 
-        // Final draw, move to next frame
-        draw_fps(params.window_width - 120., 20., 32.);
+        // Final draw in screen space, move to next frame
+        set_default_camera();
+        draw_fps(screen_width() - 120., 20., 32.);
         draw_ui(0., 20., 32., &world);
-        world.params.plant_regrow_timer += params.timestep;
-        world.params.time += params.timestep;
         next_frame().await
     }
 }
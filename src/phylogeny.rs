@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lineage {
+    /*
+     * Ancestry of one creature, kept after it dies. parents[0] is the parent
+     * the tree follows (the one that cloned itself or went looking for a
+     * mate), parents[1] the mate of a sexual birth. Founders have no parents.
+     */
+    pub parents: [Option<usize>; 2],
+    pub birth: f32,
+    pub death: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Phylogeny {
+    /*
+     * Lineage of every creature ever born, by creature ID. Without pruning
+     * this grows by one small record per birth; prune_extinct drops the
+     * records of dead creatures with no living descendants along the tree, so
+     * only the ancestry of the living population is kept (a pruned ID can
+     * still appear as the second parent of a surviving record).
+     */
+    pub lineages: BTreeMap<usize, Lineage>,
+    pub last_prune: f32, // simulation time of the last prune_extinct
+}
+
+impl Phylogeny {
+    pub fn record_birth(&mut self, id: usize, parents: [Option<usize>; 2], time: f32) {
+        self.lineages.insert(
+            id,
+            Lineage {
+                parents,
+                birth: time,
+                death: None,
+            },
+        );
+    }
+
+    pub fn record_death(&mut self, id: usize, time: f32) {
+        if let Some(lineage) = self.lineages.get_mut(&id) {
+            lineage.death = Some(time);
+        }
+    }
+
+    fn tree_parent(&self, id: usize) -> Option<usize> {
+        // The parent the tree hangs this creature from, if it is still known
+        self.lineages[&id].parents[0].filter(|p| self.lineages.contains_key(p))
+    }
+
+    pub fn prune_extinct(&mut self, time: f32) -> usize {
+        // Drop dead lineages with no living descendants, returning how many
+        // records were removed
+        let mut keep: BTreeMap<usize, ()> = BTreeMap::new();
+        let living: Vec<usize> = self
+            .lineages
+            .iter()
+            .filter(|(_, l)| l.death.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in living {
+            // Walk up until we reach an ancestor that is already kept
+            let mut current = Some(id);
+            while let Some(id) = current {
+                if keep.insert(id, ()).is_some() {
+                    break;
+                }
+                current = self.tree_parent(id);
+            }
+        }
+        let before = self.lineages.len();
+        self.lineages.retain(|id, _| keep.contains_key(id));
+        self.last_prune = time;
+        before - self.lineages.len()
+    }
+
+    pub fn to_newick(&self, now: f32) -> String {
+        // The tree in Newick format with branch lengths in simulation time.
+        // Each creature is a tip named by its ID that ends at its death (or
+        // 'now' if alive). Its offspring split off its branch at their own
+        // births, so the branch runs from split to split, one unnamed node
+        // per offspring. Founders hang from a root at the earliest founder
+        // birth
+        let mut children: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let mut roots = Vec::new();
+        for &id in self.lineages.keys() {
+            match self.tree_parent(id) {
+                Some(parent) => children.entry(parent).or_default().push(id),
+                None => roots.push(id),
+            }
+        }
+        for kids in children.values_mut() {
+            // Stable, so offspring born together stay in ID order
+            kids.sort_by(|a, b| self.lineages[a].birth.total_cmp(&self.lineages[b].birth));
+        }
+        let Some(root_time) = roots
+            .iter()
+            .map(|id| self.lineages[id].birth)
+            .min_by(f32::total_cmp)
+        else {
+            return ";".to_string();
+        };
+
+        // Written iteratively since lineages can be thousands of generations
+        // deep and have many offspring
+        enum Visit {
+            // The part of a creature's branch from 'start' on, after its
+            // first 'split' offspring have branched off
+            Branch { id: usize, split: usize, start: f32 },
+            Comma,
+            Close(f32), // end of a split node with this branch length
+        }
+        let mut out = String::from("(");
+        let mut stack = Vec::new();
+        for (i, &id) in roots.iter().enumerate().rev() {
+            stack.push(Visit::Branch {
+                id,
+                split: 0,
+                start: root_time,
+            });
+            if i > 0 {
+                stack.push(Visit::Comma);
+            }
+        }
+        while let Some(visit) = stack.pop() {
+            match visit {
+                Visit::Branch { id, split, start } => {
+                    match children.get(&id).and_then(|kids| kids.get(split)) {
+                        None => {
+                            let end = self.lineages[&id].death.unwrap_or(now);
+                            let _ = write!(out, "{}:{}", id, end - start);
+                        }
+                        Some(&kid) => {
+                            // (offspring, rest of this branch):length
+                            let birth = self.lineages[&kid].birth;
+                            out.push('(');
+                            stack.push(Visit::Close(birth - start));
+                            stack.push(Visit::Branch {
+                                id,
+                                split: split + 1,
+                                start: birth,
+                            });
+                            stack.push(Visit::Comma);
+                            stack.push(Visit::Branch {
+                                id: kid,
+                                split: 0,
+                                start: birth,
+                            });
+                        }
+                    }
+                }
+                Visit::Comma => out.push(','),
+                Visit::Close(length) => {
+                    let _ = write!(out, "):{}", length);
+                }
+            }
+        }
+        out.push_str(");");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phylogeny() -> Phylogeny {
+        // Founders 1 and 2; 1 has offspring 3 and (with 2) 4, 3 has 5 and
+        // 5 has 6. Only 1, 4 and 6 are still alive
+        let mut phylogeny = Phylogeny::default();
+        phylogeny.record_birth(1, [None, None], 0.);
+        phylogeny.record_birth(2, [None, None], 0.);
+        phylogeny.record_birth(3, [Some(1), None], 2.);
+        phylogeny.record_birth(5, [Some(3), None], 3.);
+        phylogeny.record_birth(6, [Some(5), None], 3.5);
+        phylogeny.record_birth(4, [Some(1), Some(2)], 4.);
+        phylogeny.record_death(2, 3.);
+        phylogeny.record_death(5, 4.);
+        phylogeny.record_death(3, 5.);
+        phylogeny
+    }
+
+    #[test]
+    fn offspring_split_off_at_their_birth() {
+        assert_eq!(
+            phylogeny().to_newick(10.),
+            "((((6:6.5,5:0.5):0.5,3:2):1,(4:6,1:6):2):2,2:3);"
+        );
+    }
+
+    #[test]
+    fn pruning_keeps_the_ancestry_of_the_living() {
+        // 2 is only the mate of 4, so its record goes
+        let mut phylogeny = phylogeny();
+        assert_eq!(phylogeny.prune_extinct(10.), 1);
+        assert_eq!(
+            phylogeny.to_newick(10.),
+            "((((6:6.5,5:0.5):0.5,3:2):1,(4:6,1:6):2):2);"
+        );
+    }
+
+    #[test]
+    fn empty_tree() {
+        assert_eq!(Phylogeny::default().to_newick(0.), ";");
+    }
+}
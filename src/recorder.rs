@@ -0,0 +1,138 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{Creature, World, controller::ControllerKind};
+
+type Trait = (&'static str, fn(&Creature) -> f32);
+
+// Creature traits summarised in every row as <name>_mean and <name>_var
+const TRAITS: [Trait; 14] = [
+    ("dexterity", |c| c.genome.dexterity),
+    ("hunger_threshold", |c| c.genome.hunger_threshold),
+    ("hunger_rate", |c| c.genome.hunger_rate),
+    ("hunger", |c| c.hunger),
+    ("diet", |c| c.genome.diet),
+    ("strength", |c| c.genome.strength),
+    ("boldness", |c| c.genome.boldness),
+    ("max_age", |c| c.genome.max_age),
+    ("adult_size", |c| c.genome.adult_size),
+    ("size", |c| c.size),
+    ("vision_range", |c| c.genome.vision_range),
+    ("field_of_view", |c| c.genome.field_of_view),
+    ("smell", |c| c.genome.smell),
+    ("density", |c| c.genome.density),
+];
+
+pub struct Recorder {
+    /*
+     * Samples the world every 'every' steps and writes one CSV row per sample
+     * with the population sizes (creatures split into predators and prey by
+     * Creature::is_predator, and how many have neural controllers), food
+     * totals and the mean and (population) variance of each creature trait.
+     * Trait columns are left empty while there are no creatures. Steps are
+     * the world's own step count, so a run resumed from a snapshot carries
+     * on where the saved one left off.
+     */
+    out: BufWriter<File>,
+    every: usize,
+    next_sample: usize, // world step at which 'step' writes the next row
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>, every: usize) -> io::Result<Recorder> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let mut header = vec![
+            "step",
+            "time",
+            "creatures",
+            "predators",
+            "prey",
+            "neural",
+            "plants",
+            "plant_amount",
+            "meat",
+            "meat_amount",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        for (name, _) in TRAITS {
+            header.push(format!("{}_mean", name));
+            header.push(format!("{}_var", name));
+        }
+        writeln!(out, "{}", header.join(","))?;
+        Ok(Recorder {
+            out,
+            every: every.max(1),
+            next_sample: 0,
+        })
+    }
+
+    pub fn step(&mut self, world: &World) -> io::Result<()> {
+        // Call once after every update_world; writes a row every 'every' steps
+        if world.steps >= self.next_sample {
+            self.sample(world)?;
+        }
+        Ok(())
+    }
+
+    pub fn sample(&mut self, world: &World) -> io::Result<()> {
+        // Write a row for the world as it is now
+        let step = world.steps;
+        self.next_sample = (step / self.every + 1) * self.every;
+        let plant_amount: f32 = world.plant_sources.values().map(|p| p.amount).sum();
+        let meat_amount: f32 = world.meat_sources.values().map(|m| m.amount).sum();
+        let predators = world
+            .creatures
+            .values()
+            .filter(|c| c.is_predator(&world.params))
+            .count();
+        let neural = world
+            .creatures
+            .values()
+            .filter(|c| c.genome.controller == ControllerKind::Neural)
+            .count();
+        let mut row = vec![
+            step.to_string(),
+            world.params.time.to_string(),
+            world.creatures.len().to_string(),
+            predators.to_string(),
+            (world.creatures.len() - predators).to_string(),
+            neural.to_string(),
+            world.plant_sources.len().to_string(),
+            plant_amount.to_string(),
+            world.meat_sources.len().to_string(),
+            meat_amount.to_string(),
+        ];
+        for (_, trait_of) in TRAITS {
+            match mean_var(world.creatures.values().map(trait_of)) {
+                Some((mean, var)) => {
+                    row.push(mean.to_string());
+                    row.push(var.to_string());
+                }
+                None => row.extend([String::new(), String::new()]),
+            }
+        }
+        writeln!(self.out, "{}", row.join(","))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn mean_var(values: impl Iterator<Item = f32>) -> Option<(f64, f64)> {
+    // Welford's running mean and variance, in f64 so that large populations
+    // don't lose precision
+    let (mut n, mut mean, mut m2) = (0, 0.0_f64, 0.0_f64);
+    for v in values {
+        n += 1;
+        let delta = v as f64 - mean;
+        mean += delta / n as f64;
+        m2 += delta * (v as f64 - mean);
+    }
+    (n > 0).then(|| (mean, m2 / n as f64))
+}
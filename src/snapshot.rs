@@ -0,0 +1,106 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SimRng, World};
+
+// Bump whenever anything stored in a World changes shape so that old snapshots
+// are rejected with a clear error instead of being misread
+pub const SNAPSHOT_VERSION: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotFormat {
+    Json,   // human readable, for inspecting a world
+    Binary, // compact bincode, for checkpoints
+}
+
+impl SnapshotFormat {
+    pub fn from_path(path: &Path) -> Self {
+        // '.json' files are JSON, anything else is binary
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    world: &'a World,
+    rng: &'a SimRng,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    world: World,
+    rng: SimRng,
+}
+
+fn invalid_data(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    if version == SNAPSHOT_VERSION {
+        Ok(())
+    } else {
+        Err(invalid_data(format!(
+            "snapshot version {} is not supported (expected {})",
+            version, SNAPSHOT_VERSION
+        )))
+    }
+}
+
+impl World {
+    pub fn save(&self, rng: &SimRng, path: impl AsRef<Path>) -> io::Result<()> {
+        // Write the whole world plus the RNG state, so that a loaded snapshot
+        // continues exactly like the original run would have
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        let snapshot = SnapshotRef {
+            version: SNAPSHOT_VERSION,
+            world: self,
+            rng,
+        };
+        match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, &snapshot).map_err(invalid_data)?
+            }
+            SnapshotFormat::Binary => {
+                bincode::serialize_into(&mut writer, &snapshot).map_err(invalid_data)?
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<(World, SimRng)> {
+        // The version is checked before the rest is decoded, since a different
+        // layout would otherwise fail with a confusing error
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_reader(reader).map_err(invalid_data)?;
+                let version = value["version"]
+                    .as_u64()
+                    .ok_or_else(|| invalid_data("snapshot has no version"))?;
+                check_version(version as u32)?;
+                serde_json::from_value(value).map_err(invalid_data)?
+            }
+            SnapshotFormat::Binary => {
+                let version: u32 = bincode::deserialize_from(&mut reader).map_err(invalid_data)?;
+                check_version(version)?;
+                bincode::deserialize_from(&mut reader).map_err(invalid_data)?
+            }
+        };
+        let mut world = snapshot.world;
+        world.reset_grid();
+        Ok((world, snapshot.rng))
+    }
+}
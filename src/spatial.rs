@@ -0,0 +1,214 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::Bounds;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    Creature,
+    Plant,
+    Meat,
+}
+
+impl EntityKind {
+    fn index(self) -> usize {
+        match self {
+            EntityKind::Creature => 0,
+            EntityKind::Plant => 1,
+            EntityKind::Meat => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GridGeometry {
+    /*
+     * A uniform grid of cols x rows cells tiling the world bounds, shared by
+     * everything that bins the world into cells. Positions outside the bounds
+     * are clamped into the edge cells, except that with periodic x the
+     * columns wrap around.
+     */
+    cell: Vec2, // cell width and height, chosen to tile the bounds exactly
+    origin: Vec2,
+    cols: usize,
+    rows: usize,
+    periodic_x: bool,
+}
+
+impl GridGeometry {
+    pub fn new(bounds: &Bounds, cell_size: f32, periodic_x: bool) -> Self {
+        let (width, height) = (bounds.width(), bounds.height());
+        let cols = ((width / cell_size).ceil() as usize).max(1);
+        let rows = ((height / cell_size).ceil() as usize).max(1);
+        Self {
+            cell: Vec2::new(width / cols as f32, height / rows as f32),
+            origin: Vec2::new(bounds.x_min, bounds.y_min),
+            cols,
+            rows,
+            periodic_x,
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cell(&self) -> Vec2 {
+        self.cell
+    }
+
+    pub fn periodic_x(&self) -> bool {
+        self.periodic_x
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.cols * self.rows
+    }
+
+    pub fn index(&self, (col, row): (usize, usize)) -> usize {
+        // Position of a cell in row-major storage
+        row * self.cols + col
+    }
+
+    pub fn cell_coords(&self, position: Vec2) -> (usize, usize) {
+        let (col, row) = self.raw_coords(position);
+        (self.wrap_col(col), self.clamp_row(row))
+    }
+
+    pub fn raw_coords(&self, position: Vec2) -> (isize, isize) {
+        // Cell coordinates before clamping or wrapping
+        let rel = (position - self.origin) / self.cell;
+        (rel.x.floor() as isize, rel.y.floor() as isize)
+    }
+
+    pub fn wrap_col(&self, col: isize) -> usize {
+        // Periodic grids wrap columns around, otherwise anything outside
+        // clamps to the edge column
+        let cols = self.cols as isize;
+        if self.periodic_x {
+            col.rem_euclid(cols) as usize
+        } else {
+            col.clamp(0, cols - 1) as usize
+        }
+    }
+
+    pub fn clamp_row(&self, row: isize) -> usize {
+        row.clamp(0, self.rows as isize - 1) as usize
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SpatialGrid {
+    /*
+     * Grid over the world bounds where each cell lists the IDs of the
+     * creatures, plants and meat whose position falls inside it. The grid is
+     * rebuilt once per step, so IDs can be stale (already removed) and
+     * positions up to a step old: callers look IDs up in the World maps
+     * before using them.
+     */
+    geometry: GridGeometry,
+    cells: [Vec<Vec<usize>>; 3], // one set of cells per EntityKind
+}
+
+impl SpatialGrid {
+    pub fn new(bounds: &Bounds, cell_size: f32, periodic_x: bool) -> Self {
+        let geometry = GridGeometry::new(bounds, cell_size, periodic_x);
+        Self {
+            geometry,
+            cells: std::array::from_fn(|_| vec![Vec::new(); geometry.cell_count()]),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        // The smaller cell dimension, i.e. the least distance covered per ring
+        let cell = self.geometry.cell();
+        cell.x.min(cell.y)
+    }
+
+    pub fn clear(&mut self) {
+        for cells in self.cells.iter_mut() {
+            for cell in cells.iter_mut() {
+                cell.clear();
+            }
+        }
+    }
+
+    pub fn insert(&mut self, kind: EntityKind, id: usize, position: Vec2) {
+        let i = self.geometry.index(self.geometry.cell_coords(position));
+        self.cells[kind.index()][i].push(id);
+    }
+
+    pub fn cell_coords(&self, position: Vec2) -> (usize, usize) {
+        self.geometry.cell_coords(position)
+    }
+
+    pub fn max_ring(&self) -> usize {
+        // Rings beyond this cover no cells from anywhere in the grid
+        self.geometry.cols().max(self.geometry.rows())
+    }
+
+    pub fn for_each_near(
+        &self,
+        kind: EntityKind,
+        position: Vec2,
+        radius: f32,
+        mut f: impl FnMut(usize),
+    ) {
+        // Visit every ID in the cells overlapping the square around
+        // 'position' with half-width 'radius'
+        let g = &self.geometry;
+        let (col_lo, row_lo) = g.raw_coords(position - Vec2::splat(radius));
+        let (col_hi, row_hi) = g.raw_coords(position + Vec2::splat(radius));
+        let (row_lo, row_hi) = (g.clamp_row(row_lo), g.clamp_row(row_hi));
+        let cols: Vec<usize> = if g.periodic_x() && col_hi - col_lo + 1 >= g.cols() as isize {
+            // The square wraps all the way around, visit each column once
+            (0..g.cols()).collect()
+        } else if g.periodic_x() {
+            (col_lo..=col_hi).map(|c| g.wrap_col(c)).collect()
+        } else {
+            (g.wrap_col(col_lo)..=g.wrap_col(col_hi)).collect()
+        };
+        let cells = &self.cells[kind.index()];
+        for row in row_lo..=row_hi {
+            for &col in &cols {
+                cells[g.index((col, row))].iter().for_each(|id| f(*id));
+            }
+        }
+    }
+
+    pub fn for_each_in_ring(
+        &self,
+        kind: EntityKind,
+        (col, row): (usize, usize),
+        ring: usize,
+        mut f: impl FnMut(usize),
+    ) {
+        // Visit every ID in the cells exactly 'ring' cells away (Chebyshev
+        // distance) from the cell (col, row). With periodic x a cell can come
+        // up more than once for large rings, which is fine for nearest-style
+        // searches
+        let g = &self.geometry;
+        let cells = &self.cells[kind.index()];
+        let (cols, rows) = (g.cols() as isize, g.rows() as isize);
+        let (col, row, ring) = (col as isize, row as isize, ring as isize);
+        for r in (row - ring)..=(row + ring) {
+            if r < 0 || r >= rows {
+                continue;
+            }
+            let on_edge = r == row - ring || r == row + ring;
+            let step = if on_edge || ring == 0 { 1 } else { 2 * ring };
+            let mut c = col - ring;
+            while c <= col + ring {
+                if g.periodic_x() || (c >= 0 && c < cols) {
+                    let cell = &cells[g.index((g.wrap_col(c), r as usize))];
+                    cell.iter().for_each(|id| f(*id));
+                }
+                c += step;
+            }
+        }
+    }
+}